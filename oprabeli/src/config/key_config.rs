@@ -47,6 +47,11 @@ where
 
 		KeyMap::from(vec)
 	}
+
+	/// Every action that has keys bound to it
+	pub fn actions(&self) -> impl Iterator<Item = &A> {
+		self.0.iter().map(|input_mapping| &input_mapping.action)
+	}
 }
 
 impl<A> Serialize for KeyConfig<A>
@@ -1,148 +1,807 @@
 use convert_case::{Case, Casing};
 use proc_macro::TokenStream;
-use quote::{ToTokens, format_ident, quote};
-use syn::{Data, DeriveInput, parse_macro_input};
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{ToTokens, quote};
+use std::collections::HashMap;
+use syn::{
+	Attribute, Data, DataEnum, DeriveInput, Error, Fields, Ident, Meta, Result, Variant,
+	ext::IdentExt, parse_macro_input, parse_quote,
+};
 
 #[proc_macro_derive(CommandCategory)]
 pub fn derive_command_category(input: TokenStream) -> TokenStream {
 	let input = parse_macro_input!(input as DeriveInput);
+	command_category(&input)
+		.unwrap_or_else(Error::into_compile_error)
+		.into()
+}
 
-	let Data::Enum(data) = input.data else {
-		panic!("can only derive `CommandCategory` for enum type.");
-	};
+#[proc_macro_derive(
+	Subcommand,
+	attributes(fallback_to_default, default, positional, optional, list)
+)]
+pub fn derive_subcommand(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	subcommand(&input)
+		.unwrap_or_else(Error::into_compile_error)
+		.into()
+}
 
-	let ident = format_ident!("{}", input.ident);
+#[proc_macro_derive(CommandValue, attributes(alias))]
+pub fn derive_command_value(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+	command_value(&input)
+		.unwrap_or_else(Error::into_compile_error)
+		.into()
+}
+
+fn command_category(input: &DeriveInput) -> Result<TokenStream2> {
+	let data = enum_data(input, "CommandCategory")?;
+	let ident = &input.ident;
+
+	let categories = data
+		.variants
+		.iter()
+		.map(|variant| match &variant.fields {
+			Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+				Ok((variant, kebab_name(&variant.ident), &fields.unnamed[0].ty))
+			}
+			_ => Err(Error::new_spanned(
+				&variant.ident,
+				"command category variant should wrap a single command type, like `Album(AlbumCommand)`",
+			)),
+		})
+		.collect::<Result<Vec<_>>>()?;
+	check_unique(
+		"category",
+		categories
+			.iter()
+			.map(|(variant, name, _)| (name.as_str(), variant.ident.span())),
+	)?;
+
+	let variants = categories.iter().map(|(variant, name, _)| {
+		let ident = &variant.ident;
+
+		quote! {
+			#name => Ok(Self::#ident(
+				rest.parse()
+					.map_err(|error: ParseCommandError| error.offset(offset).within(#name))?,
+			)),
+		}
+	});
+
+	let category_names = categories.iter().map(|(_, name, _)| name);
+
+	let categories_help = categories.iter().map(|(variant, name, inner)| {
+		let doc = doc_comment(&variant.attrs);
+
+		quote! {
+			::sonas_parser::schema::CategorySchema {
+				name: #name,
+				doc: #doc,
+				commands: <#inner as ::sonas_parser::schema::CommandSchema>::SCHEMA,
+			},
+		}
+	});
 
-	let variants = data.variants.iter().map(|variant| {
-		let ident = format_ident!("{}", variant.ident);
-		let name = variant.ident.to_string().to_case(Case::Kebab);
+	let display_variants = categories.iter().map(|(variant, name, _)| {
+		let ident = &variant.ident;
 
-		quote! { #name => Ok(Self::#ident(rest.parse()?)), }
+		quote! { Self::#ident(command) => write!(f, "{} {}", #name, command), }
 	});
 
-	let expanded = quote! {
+	Ok(quote! {
+		impl ::core::fmt::Display for #ident {
+			fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+				match self {
+					#(#display_variants)*
+				}
+			}
+		}
+
+		impl ::sonas_parser::schema::CommandSchema for #ident {
+			const SCHEMA: ::sonas_parser::schema::CommandsSchema =
+				::sonas_parser::schema::CommandsSchema::Categories(&[#(#categories_help)*]);
+		}
+
 		impl std::str::FromStr for #ident {
 			type Err = ::sonas_parser::ParseCommandError;
 
 			fn from_str(string: &str) -> Result<Self, Self::Err> {
 				use ::sonas_parser::*;
 
-				let (category, rest) = match string.trim().split_once(' ') {
-					Some((category, rest)) => (category, rest),
-					None => (string.trim(), ""),
-				};
+				let (category, span, offset) = tokenizer::split_word(string);
+				let rest = &string[offset..];
 
 				match category {
-					"" => Err(ParseCommandError::EmptyString),
+					"" => Err(ParseCommandErrorKind::EmptyString.at(span)),
 					#(#variants)*
-					_ => Err(ParseCommandError::UnknownCategory(category.into())),
+					_ => {
+						let suggestion = errors::suggest(category, [#(#category_names),*]);
+						Err(ParseCommandErrorKind::UnknownCategory(category.into())
+							.at(span)
+							.with_suggestion(suggestion))
+					}
 				}
 			}
 		}
-	};
-
-	expanded.into()
+	})
 }
 
-#[proc_macro_derive(Subcommand, attributes(fallback_to_default, default))]
-pub fn derive_subcommand(input: TokenStream) -> TokenStream {
-	let input = parse_macro_input!(input as DeriveInput);
+fn subcommand(input: &DeriveInput) -> Result<TokenStream2> {
+	let data = enum_data(input, "Subcommand")?;
+	let ident = &input.ident;
 
-	let Data::Enum(data) = input.data else {
-		panic!("can only derive `Subcommand` for enum type.");
-	};
+	let subcommands = data
+		.variants
+		.iter()
+		.map(Subcommand::parse)
+		.collect::<Result<Vec<_>>>()?;
+	check_unique(
+		"subcommand",
+		subcommands
+			.iter()
+			.map(|subcommand| (subcommand.name.as_str(), subcommand.variant.ident.span())),
+	)?;
 
-	let ident = format_ident!("{}", input.ident);
-
-	let variants = data.variants.iter().map(|variant| {
-		let ident = format_ident!("{}", variant.ident);
-		let name = variant.ident.to_string().to_case(Case::Kebab);
-
-		let constructor = if variant.fields.is_empty() {
-			quote! { Self::#ident }
-		} else {
-			let field_initializers = variant.fields.iter().filter_map(|field| {
-				let field_ident = field
-					.ident
-					.as_ref()
-					.map(|ident| format_ident!("{}", ident))?;
-				let field_name = field_ident.to_string().to_case(Case::Kebab);
-				let field_type = field.ty.to_token_stream().to_string();
-				let is_optional = field_type.starts_with("Option <");
-
-				let field_attrs = field
-					.attrs
-					.iter()
-					.map(|attr| attr.meta.to_token_stream().to_string())
-					.collect::<Vec<_>>();
-
-				if is_optional {
-					Some(quote! { #field_ident: args.get_optional(#field_name)?, })
-				} else if field_attrs.contains(&"fallback_to_default".to_string()) {
-					Some(quote! {
-						#field_ident: match args.get(#field_name) {
-							Ok(result) => Ok(result),
-							Err(ParseCommandError::MissingArgument(_)) => Ok(Default::default()),
-							error => error,
-						}?,
-					})
-				} else if let Some(attr) = field_attrs
-					.iter()
-					.find(|&name| name.starts_with("default("))
-				{
-					let value = syn::parse_str::<syn::Path>(
-						attr.strip_prefix("default(")
-							.and_then(|string| string.strip_suffix(")"))
-							.expect("expected default value"),
-					)
-					.unwrap()
-					.to_token_stream();
-
-					Some(
-						quote! { #field_ident: args.get_optional(#field_name)?.unwrap_or(#value), },
-					)
-				} else {
-					Some(quote! { #field_ident: args.get(#field_name)?, })
-				}
-			});
+	let variants = subcommands.iter().map(|subcommand| {
+		let ident = &subcommand.variant.ident;
+		let name = &subcommand.name;
 
-			quote! { Self::#ident { #(#field_initializers)* } }
+		let field_initializers = subcommand.arguments.iter().map(|argument| {
+			let field_ident = argument.ident;
+			let field_name = &argument.name;
+
+			let value = match (&argument.ty, &argument.default) {
+				(FieldType::List, _) => quote! { args.get_all(#field_name)? },
+				(FieldType::Optional, _) => quote! { args.get_optional(#field_name)? },
+				(FieldType::Single, Some(DefaultValue::Fallback)) => quote! {
+					match args.get(#field_name) {
+						Ok(result) => Ok(result),
+						Err(ParseCommandError {
+							kind: ParseCommandErrorKind::MissingArgument(_),
+							..
+						}) => Ok(Default::default()),
+						error => error,
+					}?
+				},
+				(FieldType::Single, Some(DefaultValue::Value(value))) => quote! {
+					args.get_optional(#field_name)?.unwrap_or_else(|| #value)
+				},
+				(FieldType::Single, None) => quote! { args.get(#field_name)? },
+			};
+
+			quote! { #field_ident: #value, }
+		});
+		let constructor = match subcommand.variant.fields {
+			Fields::Unit => quote! { Self::#ident },
+			_ => quote! { Self::#ident { #(#field_initializers)* } },
 		};
 
-		let field_strings = variant
-			.fields
+		let field_strings = subcommand.arguments.iter().map(|argument| &argument.name);
+		let list_strings = subcommand
+			.arguments
+			.iter()
+			.filter(|argument| {
+				matches!(argument.ty, FieldType::List) && argument.positional.is_none()
+			})
+			.map(|argument| &argument.name);
+		let positional_strings = subcommand
+			.arguments
 			.iter()
-			.filter_map(|field| field.ident.as_ref().map(|ident| format_ident!("{}", ident)))
-			.map(|field_name| field_name.to_string().to_case(Case::Kebab));
+			.filter(|argument| matches!(argument.positional, Some(PositionalKind::Single)))
+			.map(|argument| &argument.name);
+		let variadic_string = match subcommand
+			.arguments
+			.iter()
+			.find(|argument| matches!(argument.positional, Some(PositionalKind::Variadic)))
+		{
+			Some(argument) => {
+				let name = &argument.name;
+				quote! { Some(#name) }
+			}
+			None => quote! { None },
+		};
 
 		quote! {
 			#name => {
-				let args = Arguments::parse(rest, &[#(#field_strings),*])?;
-				Ok(#constructor)
+				let parse = || -> Result<Self, ParseCommandError> {
+					#[allow(unused_variables)]
+					let args = Arguments::parse(
+						rest,
+						&[#(#field_strings),*],
+						&[#(#list_strings),*],
+						&[#(#positional_strings),*],
+						#variadic_string,
+					)?;
+					Ok(#constructor)
+				};
+				parse().map_err(|error| error.offset(offset).within(#name))
+			}
+		}
+	});
+
+	let subcommand_names = subcommands.iter().map(|subcommand| &subcommand.name);
+
+	let subcommands_help = subcommands.iter().map(|subcommand| {
+		let name = &subcommand.name;
+		let doc = doc_comment(&subcommand.variant.attrs);
+
+		let arguments_help = subcommand.arguments.iter().map(|argument| {
+			let field_name = &argument.name;
+			let field_doc = &argument.doc;
+			let value_type = &argument.value_type;
+			let type_name = &argument.type_name;
+
+			let kind = match argument.positional {
+				None if matches!(argument.ty, FieldType::List) => {
+					quote! { ::sonas_parser::schema::ArgumentKind::List }
+				}
+				None => quote! { ::sonas_parser::schema::ArgumentKind::Named },
+				Some(PositionalKind::Single) => {
+					quote! { ::sonas_parser::schema::ArgumentKind::Positional }
+				}
+				Some(PositionalKind::Variadic) => {
+					quote! { ::sonas_parser::schema::ArgumentKind::Variadic }
+				}
+			};
+			let required = matches!(argument.ty, FieldType::Single) && argument.default.is_none();
+			let default = match &argument.default {
+				Some(DefaultValue::Value(value)) => {
+					let value = expr_string(value);
+					quote! { Some(#value) }
+				}
+				_ => quote! { None },
+			};
+
+			quote! {
+				::sonas_parser::schema::ArgumentSchema {
+					name: #field_name,
+					doc: #field_doc,
+					ty: #type_name,
+					values: <#value_type as ::sonas_parser::schema::ArgumentValues>::VALUES,
+					default: #default,
+					required: #required,
+					kind: #kind,
+				},
+			}
+		});
+
+		quote! {
+			::sonas_parser::schema::SubcommandSchema {
+				name: #name,
+				doc: #doc,
+				arguments: &[#(#arguments_help)*],
+			},
+		}
+	});
+
+	let display_variants = subcommands.iter().map(|subcommand| {
+		let ident = &subcommand.variant.ident;
+		let name = &subcommand.name;
+		let field_idents = subcommand.arguments.iter().map(|argument| argument.ident);
+
+		let positional_arguments = subcommand
+			.arguments
+			.iter()
+			.filter(|argument| argument.positional.is_some());
+		let named_arguments = subcommand
+			.arguments
+			.iter()
+			.filter(|argument| argument.positional.is_none());
+
+		let write_fields = positional_arguments.chain(named_arguments).map(|argument| {
+			let field_ident = argument.ident;
+			let field_name = &argument.name;
+			let is_optional = matches!(argument.ty, FieldType::Optional);
+
+			let write_value = match argument.positional {
+				Some(_) => quote! {
+					if positional {
+						write!(f, " {}", quote(&value.to_string()))?;
+					} else {
+						write!(f, " {}={}", #field_name, quote(&value.to_string()))?;
+					}
+				},
+				None => quote! {
+					write!(f, " {}={}", #field_name, quote(&value.to_string()))?;
+				},
+			};
+
+			match argument.positional {
+				None if matches!(argument.ty, FieldType::List) => quote! {
+					if !#field_ident.is_empty() {
+						let values = #field_ident
+							.iter()
+							.map(|value| quote(&value.to_string()).into_owned())
+							.collect::<Vec<_>>();
+						write!(f, " {}={}", #field_name, values.join(","))?;
+					}
+				},
+				Some(PositionalKind::Variadic) => quote! {
+					for value in #field_ident {
+						#write_value
+					}
+				},
+				Some(PositionalKind::Single) if is_optional => quote! {
+					match #field_ident {
+						Some(value) => { #write_value }
+						None => positional = false,
+					}
+				},
+				_ if is_optional => quote! {
+					if let Some(value) = #field_ident {
+						#write_value
+					}
+				},
+				_ => quote! {
+					let value = #field_ident;
+					#write_value
+				},
+			}
+		});
+
+		quote! {
+			#[allow(unused_mut, unused_variables)]
+			Self::#ident { #(#field_idents),* } => {
+				let mut positional = true;
+				f.write_str(#name)?;
+				#(#write_fields)*
+				Ok(())
 			}
 		}
 	});
 
-	let expanded = quote! {
+	Ok(quote! {
+		impl ::core::fmt::Display for #ident {
+			fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+				use ::sonas_parser::tokenizer::quote;
+
+				match self {
+					#(#display_variants)*
+				}
+			}
+		}
+
+		impl ::sonas_parser::schema::CommandSchema for #ident {
+			const SCHEMA: ::sonas_parser::schema::CommandsSchema =
+				::sonas_parser::schema::CommandsSchema::Subcommands(&[#(#subcommands_help)*]);
+		}
+
 		impl std::str::FromStr for #ident {
 			type Err = ::sonas_parser::ParseCommandError;
 
 			fn from_str(string: &str) -> Result<Self, Self::Err> {
 				use ::sonas_parser::*;
 
-				let (subcommand, rest) = match string.split_once(' ') {
-					Some((subcommand, rest)) => (subcommand, rest),
-					None => (string, ""),
-				};
+				let (subcommand, span, offset) = tokenizer::split_word(string);
+				let rest = &string[offset..];
 
 				match subcommand {
-					"" => Err(ParseCommandError::NoSubcommand),
+					"" => Err(ParseCommandErrorKind::NoSubcommand.at(span)),
 					#(#variants)*
-					_ => Err(ParseCommandError::UnknownSubcommand(subcommand.to_string())),
+					_ => {
+						let suggestion = errors::suggest(subcommand, [#(#subcommand_names),*]);
+						Err(ParseCommandErrorKind::UnknownSubcommand(subcommand.to_string())
+							.at(span)
+							.with_suggestion(suggestion))
+					}
+				}
+			}
+		}
+	})
+}
+
+fn command_value(input: &DeriveInput) -> Result<TokenStream2> {
+	let data = enum_data(input, "CommandValue")?;
+	let ident = &input.ident;
+
+	let values = data
+		.variants
+		.iter()
+		.map(|variant| {
+			if !matches!(variant.fields, Fields::Unit) {
+				return Err(Error::new_spanned(
+					&variant.fields,
+					"`CommandValue` variants cannot have fields",
+				));
+			}
+
+			let mut aliases = Vec::new();
+			for attr in variant
+				.attrs
+				.iter()
+				.filter(|attr| attr.path().is_ident("alias"))
+			{
+				let list = attr.parse_args_with(
+					syn::punctuated::Punctuated::<syn::LitStr, syn::Token![,]>::parse_terminated,
+				)?;
+				aliases.extend(
+					list.into_iter()
+						.map(|alias| (alias.value().to_lowercase(), alias.span())),
+				);
+			}
+
+			Ok((variant, kebab_name(&variant.ident), aliases))
+		})
+		.collect::<Result<Vec<_>>>()?;
+	check_unique(
+		"value",
+		values.iter().flat_map(|(variant, name, aliases)| {
+			std::iter::once((name.as_str(), variant.ident.span()))
+				.chain(aliases.iter().map(|(alias, span)| (alias.as_str(), *span)))
+		}),
+	)?;
+
+	let names = values.iter().map(|(_, name, _)| name);
+
+	let parse_variants = values.iter().map(|(variant, name, aliases)| {
+		let ident = &variant.ident;
+		let aliases = aliases.iter().map(|(alias, _)| alias);
+
+		quote! { #name #(| #aliases)* => Ok(Self::#ident), }
+	});
+
+	let display_variants = values.iter().map(|(variant, name, _)| {
+		let ident = &variant.ident;
+
+		quote! { Self::#ident => f.write_str(#name), }
+	});
+
+	Ok(quote! {
+		impl ::sonas_parser::schema::ArgumentValues for #ident {
+			const VALUES: &'static [&'static str] = &[#(#names),*];
+		}
+
+		impl std::str::FromStr for #ident {
+			type Err = ::sonas_parser::UnknownValueError;
+
+			fn from_str(string: &str) -> Result<Self, Self::Err> {
+				match string.to_lowercase().as_str() {
+					#(#parse_variants)*
+					_ => Err(::sonas_parser::UnknownValueError {
+						value: string.to_string(),
+						accepted: <Self as ::sonas_parser::schema::ArgumentValues>::VALUES,
+					}),
+				}
+			}
+		}
+
+		impl ::core::fmt::Display for #ident {
+			fn fmt(&self, f: &mut ::core::fmt::Formatter) -> ::core::fmt::Result {
+				match self {
+					#(#display_variants)*
 				}
 			}
 		}
+	})
+}
+
+struct Subcommand<'a> {
+	variant: &'a Variant,
+	name: String,
+	arguments: Vec<Argument<'a>>,
+}
+
+impl<'a> Subcommand<'a> {
+	fn parse(variant: &'a Variant) -> Result<Self> {
+		let arguments = match &variant.fields {
+			Fields::Named(fields) => fields
+				.named
+				.iter()
+				.map(Argument::parse)
+				.collect::<Result<Vec<_>>>()?,
+			Fields::Unnamed(fields) => {
+				return Err(Error::new_spanned(
+					fields,
+					"subcommand arguments must be named fields",
+				));
+			}
+			Fields::Unit => Vec::new(),
+		};
+
+		if let Some(variadic) = arguments
+			.iter()
+			.position(|argument| matches!(argument.positional, Some(PositionalKind::Variadic)))
+			&& let Some(argument) = arguments[variadic + 1..]
+				.iter()
+				.find(|argument| argument.positional.is_some())
+		{
+			return Err(Error::new_spanned(
+				argument.ident,
+				"variadic positional argument must be the last positional argument",
+			));
+		}
+		check_unique(
+			"argument",
+			arguments
+				.iter()
+				.map(|argument| (argument.name.as_str(), argument.ident.span())),
+		)?;
+
+		Ok(Self {
+			variant,
+			name: kebab_name(&variant.ident),
+			arguments,
+		})
+	}
+}
+
+struct Argument<'a> {
+	ident: &'a Ident,
+	name: String,
+	doc: String,
+	ty: FieldType,
+	value_type: syn::Type,
+	type_name: String,
+	positional: Option<PositionalKind>,
+	default: Option<DefaultValue>,
+}
+
+#[derive(Clone, Copy)]
+enum FieldType {
+	Single,
+	Optional,
+	List,
+}
+
+#[derive(Clone, Copy)]
+enum PositionalKind {
+	Single,
+	Variadic,
+}
+
+enum DefaultValue {
+	Fallback,
+	Value(syn::Expr),
+}
+
+impl<'a> Argument<'a> {
+	fn parse(field: &'a syn::Field) -> Result<Self> {
+		let Some(ident) = &field.ident else {
+			return Err(Error::new_spanned(
+				field,
+				"subcommand arguments must be named fields",
+			));
+		};
+
+		let mut positional = None;
+		let mut default = None;
+		let mut marker = None;
+		for attr in &field.attrs {
+			if attr.path().is_ident("positional") {
+				let kind = match &attr.meta {
+					Meta::Path(_) => PositionalKind::Single,
+					Meta::List(_) => {
+						let kind = attr.parse_args::<Ident>()?;
+						if kind != "variadic" {
+							return Err(Error::new_spanned(
+								kind,
+								"expected `#[positional]` or `#[positional(variadic)]`",
+							));
+						}
+						PositionalKind::Variadic
+					}
+					Meta::NameValue(_) => {
+						return Err(Error::new_spanned(
+							attr,
+							"expected `#[positional]` or `#[positional(variadic)]`",
+						));
+					}
+				};
+				set_once(
+					&mut positional,
+					kind,
+					attr,
+					"duplicate `#[positional]` attribute",
+				)?;
+			} else if attr.path().is_ident("fallback_to_default") {
+				attr.meta.require_path_only()?;
+				set_once(
+					&mut default,
+					DefaultValue::Fallback,
+					attr,
+					"an argument can only have one default",
+				)?;
+			} else if attr.path().is_ident("default") {
+				let value = match &attr.meta {
+					// rustc only accepts literals after `=`, so string contents are parsed as
+					// the default expression, like `#[default = "Vec::new()"]`
+					Meta::NameValue(syn::MetaNameValue {
+						value:
+							syn::Expr::Lit(syn::ExprLit {
+								lit: syn::Lit::Str(string),
+								..
+							}),
+						..
+					}) => string.parse()?,
+					Meta::NameValue(meta) => meta.value.clone(),
+					Meta::List(_) => attr.parse_args()?,
+					Meta::Path(_) => {
+						return Err(Error::new_spanned(attr, "expected `#[default = value]`"));
+					}
+				};
+				set_once(
+					&mut default,
+					DefaultValue::Value(value),
+					attr,
+					"an argument can only have one default",
+				)?;
+			} else if attr.path().is_ident("optional") || attr.path().is_ident("list") {
+				attr.meta.require_path_only()?;
+				let ty = if attr.path().is_ident("optional") {
+					FieldType::Optional
+				} else {
+					FieldType::List
+				};
+				set_once(
+					&mut marker,
+					ty,
+					attr,
+					"an argument cannot be both `#[optional]` and `#[list]`",
+				)?;
+			}
+		}
+
+		let (ty, value_type, type_name) = match marker {
+			Some((ty, _)) => {
+				let field_ty = &field.ty;
+				(
+					ty,
+					parse_quote! { <#field_ty as ::core::iter::IntoIterator>::Item },
+					type_string(field_ty),
+				)
+			}
+			None => {
+				let (ty, value_type) = field_type(&field.ty);
+				(ty, value_type.clone(), type_string(value_type))
+			}
+		};
+
+		match (ty, &positional, &default) {
+			(FieldType::Optional | FieldType::List, _, Some((_, attr))) => {
+				return Err(Error::new_spanned(
+					attr,
+					"optional and list arguments cannot have a default",
+				));
+			}
+			(FieldType::List, Some((PositionalKind::Single, attr)), _) => {
+				return Err(Error::new_spanned(
+					attr,
+					"list argument can only be positional with `#[positional(variadic)]`",
+				));
+			}
+			(
+				FieldType::Single | FieldType::Optional,
+				Some((PositionalKind::Variadic, attr)),
+				_,
+			) => {
+				return Err(Error::new_spanned(
+					attr,
+					"variadic positional argument must be a list, like `Vec<T>`",
+				));
+			}
+			_ => (),
+		}
+
+		Ok(Self {
+			ident,
+			name: kebab_name(ident),
+			doc: doc_comment(&field.attrs),
+			ty,
+			value_type,
+			type_name,
+			positional: positional.map(|(kind, _)| kind),
+			default: default.map(|(default, _)| default),
+		})
+	}
+}
+
+fn set_once<'a, T>(
+	slot: &mut Option<(T, &'a Attribute)>,
+	value: T,
+	attr: &'a Attribute,
+	message: &str,
+) -> Result<()> {
+	if slot.is_some() {
+		return Err(Error::new_spanned(attr, message));
+	}
+	*slot = Some((value, attr));
+	Ok(())
+}
+
+fn enum_data<'a>(input: &'a DeriveInput, derive: &str) -> Result<&'a DataEnum> {
+	let message = format!("can only derive `{derive}` for enum types");
+	match &input.data {
+		Data::Enum(data) => Ok(data),
+		Data::Struct(data) => Err(Error::new_spanned(data.struct_token, message)),
+		Data::Union(data) => Err(Error::new_spanned(data.union_token, message)),
+	}
+}
+
+fn kebab_name(ident: &Ident) -> String {
+	ident.unraw().to_string().to_case(Case::Kebab)
+}
+
+fn check_unique<'a>(kind: &str, names: impl IntoIterator<Item = (&'a str, Span)>) -> Result<()> {
+	let mut seen = HashMap::new();
+	let mut errors: Option<Error> = None;
+
+	for (name, span) in names {
+		let Some(&first) = seen.get(name) else {
+			seen.insert(name, span);
+			continue;
+		};
+
+		let mut error = Error::new(span, format!("{kind} name `{name}` is already used"));
+		error.combine(Error::new(first, format!("`{name}` first used here")));
+		match &mut errors {
+			Some(errors) => errors.combine(error),
+			None => errors = Some(error),
+		}
+	}
+
+	errors.map_or(Ok(()), Err)
+}
+
+fn doc_comment(attrs: &[Attribute]) -> String {
+	attrs
+		.iter()
+		.filter(|attr| attr.path().is_ident("doc"))
+		.filter_map(|attr| match &attr.meta.require_name_value().ok()?.value {
+			syn::Expr::Lit(syn::ExprLit {
+				lit: syn::Lit::Str(string),
+				..
+			}) => Some(string.value()),
+			_ => None,
+		})
+		.map(|line| line.strip_prefix(' ').map(str::to_string).unwrap_or(line))
+		.collect::<Vec<_>>()
+		.join("\n")
+		.trim()
+		.to_string()
+}
+
+fn type_string(ty: &syn::Type) -> String {
+	ty.to_token_stream().to_string().replace(' ', "")
+}
+
+fn expr_string(expr: &syn::Expr) -> String {
+	[
+		(" :: ", "::"),
+		(":: ", "::"),
+		(" (", "("),
+		("( ", "("),
+		(" )", ")"),
+		(" ,", ","),
+		(" . ", "."),
+		("! ", "!"),
+	]
+	.iter()
+	.fold(expr.to_token_stream().to_string(), |string, (from, to)| {
+		string.replace(from, to)
+	})
+}
+
+fn field_type(ty: &syn::Type) -> (FieldType, &syn::Type) {
+	let syn::Type::Path(path) = ty else {
+		return (FieldType::Single, ty);
+	};
+	let Some(segment) = path.path.segments.last() else {
+		return (FieldType::Single, ty);
+	};
+	let syn::PathArguments::AngleBracketed(arguments) = &segment.arguments else {
+		return (FieldType::Single, ty);
+	};
+	let Some(syn::GenericArgument::Type(inner)) = arguments.args.first() else {
+		return (FieldType::Single, ty);
 	};
 
-	expanded.into()
+	if segment.ident == "Option" {
+		(FieldType::Optional, inner)
+	} else if segment.ident == "Vec" {
+		(FieldType::List, inner)
+	} else {
+		(FieldType::Single, ty)
+	}
 }
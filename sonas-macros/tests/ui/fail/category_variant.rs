@@ -0,0 +1,8 @@
+use sonas_macros::CommandCategory;
+
+#[derive(CommandCategory)]
+enum Command {
+	Player { level: u8 },
+}
+
+fn main() {}
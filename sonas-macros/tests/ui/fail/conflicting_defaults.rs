@@ -0,0 +1,12 @@
+use sonas_macros::Subcommand;
+
+#[derive(Subcommand)]
+enum PlayerCommand {
+	Volume {
+		#[fallback_to_default]
+		#[default = 50]
+		level: u8,
+	},
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use sonas_macros::{CommandCategory, Subcommand};
+
+#[derive(Subcommand)]
+enum AlbumCommand {
+	List,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(CommandCategory)]
+enum Command {
+	MyAlbum(AlbumCommand),
+	My_Album(AlbumCommand),
+}
+
+fn main() {}
@@ -0,0 +1,10 @@
+use sonas_macros::Subcommand;
+
+#[allow(non_camel_case_types)]
+#[derive(Subcommand)]
+enum AlbumCommand {
+	ListTracks,
+	List_Tracks,
+}
+
+fn main() {}
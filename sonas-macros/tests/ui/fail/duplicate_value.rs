@@ -0,0 +1,11 @@
+use sonas_macros::CommandValue;
+
+#[derive(CommandValue)]
+enum SortDirection {
+	#[alias("a", "asc")]
+	Ascending,
+	#[alias("d", "A")]
+	Descending,
+}
+
+fn main() {}
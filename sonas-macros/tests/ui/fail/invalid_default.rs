@@ -0,0 +1,11 @@
+use sonas_macros::Subcommand;
+
+#[derive(Subcommand)]
+enum PlayerCommand {
+	Volume {
+		#[default = "50 +"]
+		level: u8,
+	},
+}
+
+fn main() {}
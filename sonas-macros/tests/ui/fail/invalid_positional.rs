@@ -0,0 +1,11 @@
+use sonas_macros::Subcommand;
+
+#[derive(Subcommand)]
+enum PlayerCommand {
+	Seek {
+		#[positional(optional)]
+		to: u32,
+	},
+}
+
+fn main() {}
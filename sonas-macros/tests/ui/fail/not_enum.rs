@@ -0,0 +1,8 @@
+use sonas_macros::Subcommand;
+
+#[derive(Subcommand)]
+struct PlayerCommand {
+	level: u8,
+}
+
+fn main() {}
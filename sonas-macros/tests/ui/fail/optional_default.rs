@@ -0,0 +1,11 @@
+use sonas_macros::Subcommand;
+
+#[derive(Subcommand)]
+enum PlayerCommand {
+	Volume {
+		#[default = 50]
+		level: Option<u8>,
+	},
+}
+
+fn main() {}
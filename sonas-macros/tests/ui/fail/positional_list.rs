@@ -0,0 +1,11 @@
+use sonas_macros::Subcommand;
+
+#[derive(Subcommand)]
+enum QueueCommand {
+	Add {
+		#[positional]
+		ids: Vec<usize>,
+	},
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use sonas_macros::Subcommand;
+
+#[derive(Subcommand)]
+enum PlayerCommand {
+	Volume(u8),
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use sonas_macros::Subcommand;
+
+#[derive(Subcommand)]
+enum QueueCommand {
+	Insert {
+		#[positional(variadic)]
+		ids: Vec<usize>,
+		#[positional]
+		at: usize,
+	},
+}
+
+fn main() {}
@@ -0,0 +1,45 @@
+use sonas_macros::Subcommand;
+
+type MaybeId = Option<usize>;
+type Ids = Vec<usize>;
+
+fn default_volume() -> u8 {
+	50
+}
+
+#[derive(Debug, PartialEq, Subcommand)]
+enum PlayerCommand {
+	Volume {
+		#[default = "default_volume()"]
+		level: u8,
+		#[default = 3]
+		step: u8,
+		#[default(String::from("main"))]
+		output: String,
+	},
+	Play {
+		#[positional]
+		#[optional]
+		id: MaybeId,
+		#[list]
+		queue: Ids,
+	},
+}
+
+fn main() {
+	assert_eq!(
+		"volume".parse(),
+		Ok(PlayerCommand::Volume {
+			level: 50,
+			step: 3,
+			output: "main".to_string(),
+		})
+	);
+	assert_eq!(
+		"play queue=1,2".parse(),
+		Ok(PlayerCommand::Play {
+			id: None,
+			queue: vec![1, 2],
+		})
+	);
+}
@@ -1,56 +1,283 @@
-use crate::errors::ParseCommandError;
-use std::{collections::HashMap, str::FromStr};
+use crate::{
+	errors::{ParseCommandError, ParseCommandErrorKind, suggest},
+	tokenizer,
+};
+use core::ops::Range;
+use std::{collections::HashMap, fmt::Display, str::FromStr};
 
-#[derive(Debug, Clone)]
-pub struct Arguments<'a>(HashMap<&'a str, &'a str>);
+#[derive(Debug, Clone, Default)]
+pub struct Arguments {
+	values: HashMap<String, Vec<(String, Range<usize>)>>,
+	end: usize,
+}
+
+impl Arguments {
+	pub fn parse(
+		string: &str,
+		options: &[&str],
+		lists: &[&str],
+		positional: &[&str],
+		variadic: Option<&str>,
+	) -> Result<Self, ParseCommandError> {
+		let mut values = HashMap::<String, Vec<(String, Range<usize>)>>::new();
+		let mut positional = positional.iter();
+		let mut seen_named = false;
 
-impl<'a> Arguments<'a> {
-	pub fn parse(string: &'a str, options: &[&str]) -> Result<Self, ParseCommandError> {
-		let mut result = HashMap::new();
+		for token in tokenizer::tokenize(string)? {
+			let Some(key) = token.key else {
+				if seen_named {
+					return Err(
+						ParseCommandErrorKind::PositionalAfterNamed(token.value).at(token.span)
+					);
+				}
 
-		for arg in string.trim().split(' ').filter(|s| !s.is_empty()) {
-			let Some((key, value)) = arg.split_once('=') else {
-				return Err(ParseCommandError::InvalidArgument(arg.to_string()));
+				let Some(&name) = positional.next().or(variadic.as_ref()) else {
+					return Err(
+						ParseCommandErrorKind::UnexpectedPositional(token.value).at(token.span)
+					);
+				};
+
+				values
+					.entry(name.to_string())
+					.or_default()
+					.push((token.value, token.value_span));
+				continue;
 			};
 
-			if result.contains_key(key) {
-				return Err(ParseCommandError::DuplicateArgument(key.to_string()));
+			seen_named = true;
+			let key_span = token.span.start..token.value_span.start - 1;
+
+			if !options.contains(&key.as_str()) {
+				let suggestion = suggest(&key, options.iter().copied());
+				return Err(ParseCommandErrorKind::UnexpectedArgument(key)
+					.at(key_span)
+					.with_suggestion(suggestion));
 			}
 
-			result.insert(key, value);
-		}
+			if lists.contains(&key.as_str()) {
+				let start = token.value_span.start;
+				let items = tokenizer::split_list(&string[token.value_span])
+					.map_err(|error| error.offset(start))?;
+				values.entry(key).or_default().extend(
+					items
+						.into_iter()
+						.map(|(value, span)| (value, span.start + start..span.end + start)),
+				);
+				continue;
+			}
 
-		for key in result.keys() {
-			if !options.contains(key) {
-				return Err(ParseCommandError::UnexpectedArgument(key.to_string()));
+			if values.contains_key(&key) && variadic != Some(key.as_str()) {
+				return Err(ParseCommandErrorKind::DuplicateArgument(key).at(key_span));
 			}
+
+			values
+				.entry(key)
+				.or_default()
+				.push((token.value, token.value_span));
 		}
 
-		Ok(result.into())
+		Ok(Self {
+			values,
+			end: string.trim_end().len(),
+		})
 	}
 
-	pub fn get<T: FromStr>(&self, name: &str) -> Result<T, ParseCommandError> {
-		let Ok(Some(value)) = self.get_optional(name) else {
-			return Err(ParseCommandError::MissingArgument(name.to_string()));
-		};
+	pub fn offset(mut self, offset: usize) -> Self {
+		for (_, span) in self.values.values_mut().flatten() {
+			*span = span.start + offset..span.end + offset;
+		}
+		self.end += offset;
+		self
+	}
 
-		Ok(value)
+	pub fn get<T: FromStr<Err: Display>>(&self, name: &str) -> Result<T, ParseCommandError> {
+		match self.get_optional(name)? {
+			Some(value) => Ok(value),
+			None => {
+				Err(ParseCommandErrorKind::MissingArgument(name.to_string()).at(self.end..self.end))
+			}
+		}
 	}
 
-	pub fn get_optional<T: FromStr>(&self, name: &str) -> Result<Option<T>, ParseCommandError> {
-		let Some(value) = self.0.get(name) else {
+	pub fn get_optional<T: FromStr<Err: Display>>(
+		&self,
+		name: &str,
+	) -> Result<Option<T>, ParseCommandError> {
+		let Some((value, span)) = self.values.get(name).and_then(|values| values.first()) else {
 			return Ok(None);
 		};
 
 		value
 			.parse()
-			.map_err(|_| ParseCommandError::InvalidArgument(name.to_string()))
+			.map_err(|error| invalid_argument(name, span, error))
 			.map(Some)
 	}
+
+	pub fn get_all<T: FromStr<Err: Display>>(
+		&self,
+		name: &str,
+	) -> Result<Vec<T>, ParseCommandError> {
+		self.values
+			.get(name)
+			.into_iter()
+			.flatten()
+			.map(|(value, span)| {
+				value
+					.parse()
+					.map_err(|error| invalid_argument(name, span, error))
+			})
+			.collect()
+	}
+}
+
+fn invalid_argument(name: &str, span: &Range<usize>, error: impl Display) -> ParseCommandError {
+	ParseCommandErrorKind::InvalidArgument {
+		name: name.to_string(),
+		reason: error.to_string(),
+	}
+	.at(span.clone())
 }
 
-impl<'a> From<HashMap<&'a str, &'a str>> for Arguments<'a> {
-	fn from(value: HashMap<&'a str, &'a str>) -> Self {
-		Self(value)
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_quoted_values() -> Result<(), ParseCommandError> {
+		let args = Arguments::parse(
+			r#"name="Late Night Jazz" path='/music/Kind of Blue'"#,
+			&["name", "path"],
+			&[],
+			&[],
+			None,
+		)?;
+
+		assert_eq!(args.get::<String>("name")?, "Late Night Jazz");
+		assert_eq!(args.get::<String>("path")?, "/music/Kind of Blue");
+
+		Ok(())
+	}
+
+	#[test]
+	fn parses_positional_values() -> Result<(), ParseCommandError> {
+		let options = ["id", "name", "sort"];
+		let args = Arguments::parse(
+			"5 'Kind of Blue' sort=asc",
+			&options,
+			&[],
+			&["id", "name"],
+			None,
+		)?;
+
+		assert_eq!(args.get::<usize>("id")?, 5);
+		assert_eq!(args.get::<String>("name")?, "Kind of Blue");
+		assert_eq!(args.get::<String>("sort")?, "asc");
+
+		let args = Arguments::parse("5 name=Blue", &options, &[], &["id", "name"], None)?;
+
+		assert_eq!(args.get::<usize>("id")?, 5);
+		assert_eq!(args.get::<String>("name")?, "Blue");
+
+		Ok(())
+	}
+
+	#[test]
+	fn parses_variadic_values() -> Result<(), ParseCommandError> {
+		let options = ["id", "paths"];
+		let args = Arguments::parse("3 a.flac 'b c.flac'", &options, &[], &["id"], Some("paths"))?;
+
+		assert_eq!(args.get::<usize>("id")?, 3);
+		assert_eq!(args.get_all::<String>("paths")?, vec!["a.flac", "b c.flac"]);
+
+		let args = Arguments::parse("3", &options, &[], &["id"], Some("paths"))?;
+
+		assert_eq!(args.get_all::<String>("paths")?, Vec::<String>::new());
+
+		Ok(())
+	}
+
+	#[test]
+	fn parses_list_values() -> Result<(), ParseCommandError> {
+		let options = ["ids", "genre", "name"];
+		let lists = ["ids", "genre"];
+		let args = Arguments::parse(
+			r#"ids=3,7,9 genre=Jazz genre="Soul, Funk",'R&B' name=a,b"#,
+			&options,
+			&lists,
+			&[],
+			None,
+		)?;
+
+		assert_eq!(args.get_all::<usize>("ids")?, vec![3, 7, 9]);
+		assert_eq!(
+			args.get_all::<String>("genre")?,
+			vec!["Jazz", "Soul, Funk", "R&B"]
+		);
+		assert_eq!(args.get::<String>("name")?, "a,b");
+
+		let args = Arguments::parse("ids= genre=\"\"", &options, &lists, &[], None)?;
+
+		assert_eq!(args.get_all::<usize>("ids")?, Vec::<usize>::new());
+		assert_eq!(args.get_all::<String>("genre")?, vec![""]);
+
+		let args = Arguments::parse("ids=3,x,9", &options, &lists, &[], None)?;
+
+		assert_eq!(
+			args.get_all::<usize>("ids").map_err(|error| error.span),
+			Err(6..7)
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn invalid_input_errors() {
+		assert_eq!(
+			Arguments::parse("a=1 a=2", &["a"], &[], &[], None).map(|_| ()),
+			Err(ParseCommandErrorKind::DuplicateArgument("a".to_string()).at(4..5))
+		);
+		assert_eq!(
+			Arguments::parse("b=1", &["a"], &[], &[], None).map(|_| ()),
+			Err(ParseCommandErrorKind::UnexpectedArgument("b".to_string()).at(0..1))
+		);
+		assert_eq!(
+			Arguments::parse("\"a=1\"", &["a"], &[], &[], None).map(|_| ()),
+			Err(ParseCommandErrorKind::UnexpectedPositional("a=1".to_string()).at(0..5))
+		);
+		assert_eq!(
+			Arguments::parse("1 2", &["a"], &[], &["a"], None).map(|_| ()),
+			Err(ParseCommandErrorKind::UnexpectedPositional("2".to_string()).at(2..3))
+		);
+		assert_eq!(
+			Arguments::parse("b=2 1", &["a", "b"], &[], &["a"], None).map(|_| ()),
+			Err(ParseCommandErrorKind::PositionalAfterNamed("1".to_string()).at(4..5))
+		);
+		assert_eq!(
+			Arguments::parse("1 a=2", &["a"], &[], &["a"], None).map(|_| ()),
+			Err(ParseCommandErrorKind::DuplicateArgument("a".to_string()).at(2..3))
+		);
+	}
+
+	#[test]
+	fn errors_point_at_values() -> Result<(), ParseCommandError> {
+		let args = Arguments::parse("id=five", &["id", "name"], &[], &[], None)?.offset(6);
+
+		assert_eq!(
+			args.get::<usize>("id").map_err(|error| error.span),
+			Err(9..13)
+		);
+		assert_eq!(
+			args.get::<String>("name").map_err(|error| error.span),
+			Err(13..13)
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn suggests_argument_names() {
+		let error = Arguments::parse("srot=asc", &["sort", "id"], &[], &[], None).unwrap_err();
+
+		assert_eq!(error.suggestion.as_deref(), Some("sort"));
 	}
 }
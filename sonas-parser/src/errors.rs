@@ -1,7 +1,26 @@
+use core::ops::Range;
+use std::fmt::Write as _;
 use thiserror::Error;
 
 #[derive(Debug, Clone, Eq, PartialEq, Error)]
-pub enum ParseCommandError {
+#[error("{kind}{}", display_path(.path))]
+pub struct ParseCommandError {
+	pub kind: ParseCommandErrorKind,
+	pub span: Range<usize>,
+	pub suggestion: Option<String>,
+	pub path: Vec<String>,
+}
+
+fn display_path(path: &[String]) -> String {
+	if path.is_empty() {
+		String::new()
+	} else {
+		format!(" (in '{}')", path.join(" "))
+	}
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Error)]
+pub enum ParseCommandErrorKind {
 	#[error("given input was empty")]
 	EmptyString,
 	#[error("no subcommand was specified")]
@@ -10,12 +29,189 @@ pub enum ParseCommandError {
 	UnknownCategory(String),
 	#[error("unknown subcommand '{0}'")]
 	UnknownSubcommand(String),
-	#[error("invalid value specified for argument '{0}'")]
-	InvalidArgument(String),
+	#[error("invalid value specified for argument '{name}': {reason}")]
+	InvalidArgument { name: String, reason: String },
 	#[error("duplicate argument specified '{0}'")]
 	DuplicateArgument(String),
 	#[error("unexpected argument '{0}' was specified")]
 	UnexpectedArgument(String),
 	#[error("missing argument '{0}'")]
 	MissingArgument(String),
+	#[error("unexpected positional argument '{0}'")]
+	UnexpectedPositional(String),
+	#[error("positional argument '{0}' must come before named arguments")]
+	PositionalAfterNamed(String),
+	#[error("unterminated quote")]
+	UnterminatedQuote,
+	#[error("trailing escape character")]
+	TrailingEscape,
+}
+
+impl ParseCommandErrorKind {
+	pub fn at(self, span: Range<usize>) -> ParseCommandError {
+		ParseCommandError {
+			kind: self,
+			span,
+			suggestion: None,
+			path: Vec::new(),
+		}
+	}
+}
+
+impl ParseCommandError {
+	pub fn with_suggestion(mut self, suggestion: Option<&str>) -> Self {
+		self.suggestion = suggestion.map(str::to_string);
+		self
+	}
+
+	pub fn within(mut self, name: &str) -> Self {
+		self.path.insert(0, name.to_string());
+		self
+	}
+
+	pub fn within_path(mut self, path: &[&str]) -> Self {
+		self.path
+			.splice(0..0, path.iter().map(|name| name.to_string()));
+		self
+	}
+
+	pub fn offset(mut self, offset: usize) -> Self {
+		self.span = self.span.start + offset..self.span.end + offset;
+		self
+	}
+
+	pub fn diagnostic(&self, input: &str) -> String {
+		let start = self.span.start.min(input.len());
+		let end = self.span.end.clamp(start, input.len());
+
+		let line_start = input[..start].rfind('\n').map_or(0, |i| i + 1);
+		let line_end = input[start..].find('\n').map_or(input.len(), |i| start + i);
+		let line = &input[line_start..line_end];
+
+		let padding = input[line_start..start].chars().count();
+		let width = input[start..end.min(line_end)].chars().count().max(1);
+
+		let mut text = format!("error: {self}\n");
+		let _ = writeln!(text, "  | {line}");
+		let _ = write!(text, "  | {}{}", " ".repeat(padding), "^".repeat(width));
+		if let Some(suggestion) = &self.suggestion {
+			let _ = write!(text, " did you mean '{suggestion}'?");
+		}
+		text.push('\n');
+		text
+	}
+}
+
+pub fn suggest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+	candidates
+		.into_iter()
+		.map(|candidate| (edit_distance(name, candidate), candidate))
+		.filter(|&(distance, candidate)| {
+			let length = candidate.chars().count();
+			distance < length && distance <= (length / 3).max(1)
+		})
+		.min_by_key(|&(distance, _)| distance)
+		.map(|(_, candidate)| candidate)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+	let a = a.chars().collect::<Vec<_>>();
+	let b = b.chars().collect::<Vec<_>>();
+	let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+	for (i, row) in distances.iter_mut().enumerate() {
+		row[0] = i;
+	}
+	for (j, distance) in distances[0].iter_mut().enumerate() {
+		*distance = j;
+	}
+
+	for i in 1..=a.len() {
+		for j in 1..=b.len() {
+			let cost = usize::from(a[i - 1] != b[j - 1]);
+			let mut distance = (distances[i - 1][j] + 1)
+				.min(distances[i][j - 1] + 1)
+				.min(distances[i - 1][j - 1] + cost);
+			if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+				distance = distance.min(distances[i - 2][j - 2] + 1);
+			}
+			distances[i][j] = distance;
+		}
+	}
+
+	distances[a.len()][b.len()]
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Error)]
+#[error("unknown value '{value}', expected one of: {}", accepted.join(", "))]
+pub struct UnknownValueError {
+	pub value: String,
+	pub accepted: &'static [&'static str],
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Error)]
+pub enum InvalidValueError {
+	#[error("invalid value '{value}': {reason}")]
+	Invalid { value: String, reason: String },
+	#[error("missing value after sign in '{0}'")]
+	MissingValue(String),
+	#[error("expected a percentage like '50%', found '{0}'")]
+	NotAPercentage(String),
+	#[error("percentage '{0}' must be between 0% and 100%")]
+	PercentageOutOfRange(String),
+	#[error("invalid time '{0}', expected e.g. '1:23', '90', '10s' or '1m30s'")]
+	InvalidTime(String),
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn suggests_nearest_name() {
+		let names = ["album", "artist", "playlist"];
+
+		assert_eq!(suggest("albm", names), Some("album"));
+		assert_eq!(suggest("artst", names), Some("artist"));
+		assert_eq!(suggest("playlsit", names), Some("playlist"));
+		assert_eq!(suggest("srot", ["sort", "id"]), Some("sort"));
+		assert_eq!(suggest("queue", names), None);
+	}
+
+	#[test]
+	fn renders_caret_diagnostic() {
+		let error = ParseCommandErrorKind::UnknownCategory("albm".to_string())
+			.at(0..4)
+			.with_suggestion(Some("album"));
+
+		assert_eq!(
+			error.diagnostic("albm list"),
+			"error: unknown command category 'albm'\n  \
+			| albm list\n  \
+			| ^^^^ did you mean 'album'?\n"
+		);
+
+		let error = ParseCommandErrorKind::NoSubcommand.at(5..5);
+
+		assert_eq!(
+			error.diagnostic("album"),
+			"error: no subcommand was specified\n  \
+			| album\n  \
+			|      ^\n"
+		);
+	}
+
+	#[test]
+	fn diagnostic_points_at_span_line() {
+		let error = ParseCommandErrorKind::UnexpectedArgument("srot".to_string())
+			.at(22..26)
+			.with_suggestion(Some("sort"));
+
+		assert_eq!(
+			error.diagnostic("album list\nalbum list srot=asc"),
+			"error: unexpected argument 'srot' was specified\n  \
+			| album list srot=asc\n  \
+			|            ^^^^ did you mean 'sort'?\n"
+		);
+	}
 }
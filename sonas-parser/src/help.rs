@@ -0,0 +1,325 @@
+use crate::{
+	errors::{ParseCommandError, ParseCommandErrorKind, suggest},
+	schema::{CommandSchema, CommandsSchema, SubcommandSchema},
+	tokenizer,
+};
+use core::fmt::Write as _;
+
+pub fn help<T: CommandSchema>(path: &str) -> Result<String, ParseCommandError> {
+	let mut commands = T::SCHEMA;
+	let mut doc = "";
+	let mut prefix = Vec::new();
+
+	for word in tokenizer::tokenize(path)? {
+		match commands {
+			CommandsSchema::Categories(categories) => {
+				let Some(category) = categories.iter().find(|c| c.name == word.value) else {
+					let suggestion = suggest(&word.value, commands.names());
+					return Err(ParseCommandErrorKind::UnknownCategory(word.value)
+						.at(word.span)
+						.with_suggestion(suggestion)
+						.within_path(&prefix));
+				};
+				commands = category.commands;
+				doc = category.doc;
+				prefix.push(category.name);
+			}
+			CommandsSchema::Subcommands(subcommands) => {
+				let Some(subcommand) = subcommands.iter().find(|s| s.name == word.value) else {
+					let suggestion = suggest(&word.value, commands.names());
+					return Err(ParseCommandErrorKind::UnknownSubcommand(word.value)
+						.at(word.span)
+						.with_suggestion(suggestion)
+						.within_path(&prefix));
+				};
+				return Ok(subcommand_help(&prefix.join(" "), subcommand));
+			}
+		}
+	}
+
+	Ok(commands_help(&prefix.join(" "), doc, commands))
+}
+
+pub fn usage<T: CommandSchema>(input: &str) -> String {
+	let words = tokenizer::tokenize(input)
+		.unwrap_or_default()
+		.into_iter()
+		.take_while(|token| token.key.is_none())
+		.map(|token| token.value)
+		.collect::<Vec<_>>();
+
+	(0..=words.len())
+		.rev()
+		.find_map(|len| help::<T>(&words[..len].join(" ")).ok())
+		.unwrap_or_else(|| commands_help("", "", T::SCHEMA))
+}
+
+fn commands_help(path: &str, doc: &str, commands: CommandsSchema) -> String {
+	let mut text = String::new();
+	if !doc.is_empty() {
+		let _ = writeln!(text, "{doc}\n");
+	}
+
+	let path = if path.is_empty() {
+		String::new()
+	} else {
+		format!("{path} ")
+	};
+	let (usage, heading, rows) = match commands {
+		CommandsSchema::Categories(categories) => (
+			"<category> <subcommand>",
+			"Categories",
+			categories
+				.iter()
+				.map(|c| (c.name, c.doc))
+				.collect::<Vec<_>>(),
+		),
+		CommandsSchema::Subcommands(subcommands) => (
+			"<subcommand>",
+			"Subcommands",
+			subcommands.iter().map(|s| (s.name, s.doc)).collect(),
+		),
+	};
+
+	let _ = writeln!(text, "Usage: {path}{usage} [arguments...]");
+	let _ = writeln!(text, "\n{heading}:");
+	write_table(
+		&mut text,
+		rows.into_iter()
+			.map(|(name, doc)| (name.to_string(), summary(doc))),
+	);
+	text
+}
+
+fn subcommand_help(path: &str, subcommand: &SubcommandSchema) -> String {
+	let mut text = String::new();
+	if !subcommand.doc.is_empty() {
+		let _ = writeln!(text, "{}\n", subcommand.doc);
+	}
+	let _ = writeln!(text, "Usage: {}", subcommand.usage(path));
+
+	if !subcommand.arguments.is_empty() {
+		text.push_str("\nArguments:\n");
+		write_table(
+			&mut text,
+			subcommand.arguments.iter().map(|arg| {
+				let mut description = summary(arg.doc).to_string();
+				let _ = write!(description, " [type: {}]", arg.ty);
+				if !arg.values.is_empty() {
+					let _ = write!(description, " [values: {}]", arg.values.join(", "));
+				}
+				if let Some(default) = arg.default {
+					let _ = write!(description, " [default: {default}]");
+				}
+				(arg.usage(), description.trim_start().to_string())
+			}),
+		);
+	}
+	text
+}
+
+fn summary(doc: &str) -> &str {
+	doc.lines().next().unwrap_or_default()
+}
+
+fn write_table(text: &mut String, rows: impl Iterator<Item = (String, impl AsRef<str>)>) {
+	let rows = rows.collect::<Vec<_>>();
+	let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+
+	for (name, description) in rows {
+		let description = description.as_ref();
+		let line = format!("  {name:width$}    {description}");
+		let _ = writeln!(text, "{}", line.trim_end());
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::schema::*;
+
+	struct TestCommand;
+
+	impl CommandSchema for TestCommand {
+		const SCHEMA: CommandsSchema = CommandsSchema::Categories(&[
+			CategorySchema {
+				name: "album",
+				doc: "Browse albums\nin the library",
+				commands: CommandsSchema::Subcommands(&[
+					SubcommandSchema {
+						name: "list",
+						doc: "List all albums",
+						arguments: &[ArgumentSchema {
+							name: "sort",
+							doc: "Sort direction",
+							ty: "SortDirection",
+							values: &["ascending", "descending"],
+							default: Some("SortDirection::Descending"),
+							required: false,
+							kind: ArgumentKind::Named,
+						}],
+					},
+					SubcommandSchema {
+						name: "list-tracks",
+						doc: "",
+						arguments: &[ArgumentSchema {
+							name: "id",
+							doc: "",
+							ty: "usize",
+							values: &[],
+							default: None,
+							required: true,
+							kind: ArgumentKind::Positional,
+						}],
+					},
+				]),
+			},
+			CategorySchema {
+				name: "player",
+				doc: "Control the player",
+				commands: CommandsSchema::Categories(&[CategorySchema {
+					name: "output",
+					doc: "Configure the audio output",
+					commands: CommandsSchema::Subcommands(&[SubcommandSchema {
+						name: "set",
+						doc: "Select the output device",
+						arguments: &[ArgumentSchema {
+							name: "device",
+							doc: "",
+							ty: "String",
+							values: &[],
+							default: None,
+							required: true,
+							kind: ArgumentKind::Positional,
+						}],
+					}]),
+				}]),
+			},
+		]);
+	}
+
+	#[test]
+	fn renders_overview() -> Result<(), ParseCommandError> {
+		assert_eq!(
+			help::<TestCommand>("")?,
+			"Usage: <category> <subcommand> [arguments...]\n\
+			\n\
+			Categories:\n  \
+			album     Browse albums\n  \
+			player    Control the player\n"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn renders_category() -> Result<(), ParseCommandError> {
+		assert_eq!(
+			help::<TestCommand>("album")?,
+			"Browse albums\nin the library\n\
+			\n\
+			Usage: album <subcommand> [arguments...]\n\
+			\n\
+			Subcommands:\n  \
+			list           List all albums\n  \
+			list-tracks\n"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn renders_subcommand() -> Result<(), ParseCommandError> {
+		assert_eq!(
+			help::<TestCommand>("album list")?,
+			"List all albums\n\
+			\n\
+			Usage: album list [sort=<sort>]\n\
+			\n\
+			Arguments:\n  \
+			[sort=<sort>]    Sort direction [type: SortDirection] \
+			[values: ascending, descending] [default: SortDirection::Descending]\n"
+		);
+		assert_eq!(
+			help::<TestCommand>("album list-tracks")?,
+			"Usage: album list-tracks <id>\n\
+			\n\
+			Arguments:\n  \
+			<id>    [type: usize]\n"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn renders_nested_categories() -> Result<(), ParseCommandError> {
+		assert_eq!(
+			help::<TestCommand>("player")?,
+			"Control the player\n\
+			\n\
+			Usage: player <category> <subcommand> [arguments...]\n\
+			\n\
+			Categories:\n  \
+			output    Configure the audio output\n"
+		);
+		assert_eq!(
+			help::<TestCommand>("player output")?,
+			"Configure the audio output\n\
+			\n\
+			Usage: player output <subcommand> [arguments...]\n\
+			\n\
+			Subcommands:\n  \
+			set    Select the output device\n"
+		);
+		assert_eq!(
+			help::<TestCommand>("player output set")?,
+			"Select the output device\n\
+			\n\
+			Usage: player output set <device>\n\
+			\n\
+			Arguments:\n  \
+			<device>    [type: String]\n"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn usage_falls_back_to_closest_match() {
+		assert_eq!(
+			usage::<TestCommand>("album list sort=sideways"),
+			help::<TestCommand>("album list").unwrap()
+		);
+		assert_eq!(
+			usage::<TestCommand>("album shuffle"),
+			help::<TestCommand>("album").unwrap()
+		);
+		assert_eq!(
+			usage::<TestCommand>("artist list"),
+			help::<TestCommand>("").unwrap()
+		);
+		assert_eq!(
+			usage::<TestCommand>("player output sett"),
+			help::<TestCommand>("player output").unwrap()
+		);
+	}
+
+	#[test]
+	fn unknown_path_errors() {
+		assert_eq!(
+			help::<TestCommand>("artist"),
+			Err(ParseCommandErrorKind::UnknownCategory("artist".to_string()).at(0..6))
+		);
+		assert_eq!(
+			help::<TestCommand>("album lst"),
+			Err(ParseCommandErrorKind::UnknownSubcommand("lst".to_string())
+				.at(6..9)
+				.with_suggestion(Some("list"))
+				.within("album"))
+		);
+		assert_eq!(
+			help::<TestCommand>("player outptu").map_err(|error| error.to_string()),
+			Err("unknown command category 'outptu' (in 'player')".to_string())
+		);
+	}
+}
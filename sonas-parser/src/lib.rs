@@ -1,5 +1,10 @@
 pub mod arguments;
 pub mod errors;
+pub mod help;
+pub mod schema;
+pub mod tokenizer;
+pub mod values;
 
 pub use arguments::Arguments;
-pub use errors::ParseCommandError;
+pub use errors::{InvalidValueError, ParseCommandError, ParseCommandErrorKind, UnknownValueError};
+pub use values::{Percentage, Relative, TimeOffset, Timestamp};
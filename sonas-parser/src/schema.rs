@@ -0,0 +1,133 @@
+use serde::Serialize;
+
+pub trait CommandSchema {
+	const SCHEMA: CommandsSchema;
+}
+
+pub trait ArgumentValues {
+	const VALUES: &'static [&'static str] = &[];
+}
+
+macro_rules! impl_argument_values {
+	($($ty:ty),*) => {
+		$(impl ArgumentValues for $ty {})*
+	};
+}
+
+impl_argument_values!(
+	u8,
+	u16,
+	u32,
+	u64,
+	u128,
+	usize,
+	i8,
+	i16,
+	i32,
+	i64,
+	i128,
+	isize,
+	f32,
+	f64,
+	char,
+	String,
+	std::path::PathBuf
+);
+
+impl ArgumentValues for bool {
+	const VALUES: &'static [&'static str] = &["true", "false"];
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CommandsSchema {
+	Categories(&'static [CategorySchema]),
+	Subcommands(&'static [SubcommandSchema]),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+pub struct CategorySchema {
+	pub name: &'static str,
+	pub doc: &'static str,
+	pub commands: CommandsSchema,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+pub struct SubcommandSchema {
+	pub name: &'static str,
+	pub doc: &'static str,
+	pub arguments: &'static [ArgumentSchema],
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+pub struct ArgumentSchema {
+	pub name: &'static str,
+	pub doc: &'static str,
+	pub ty: &'static str,
+	pub values: &'static [&'static str],
+	pub default: Option<&'static str>,
+	pub required: bool,
+	pub kind: ArgumentKind,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArgumentKind {
+	Named,
+	Positional,
+	Variadic,
+	List,
+}
+
+impl ArgumentKind {
+	pub fn is_positional(self) -> bool {
+		matches!(self, Self::Positional | Self::Variadic)
+	}
+}
+
+impl ArgumentSchema {
+	pub fn usage(&self) -> String {
+		let usage = match self.kind {
+			ArgumentKind::Named => format!("{}=<{}>", self.name, self.name),
+			ArgumentKind::Positional => format!("<{}>", self.name),
+			ArgumentKind::Variadic => format!("<{}>...", self.name),
+			ArgumentKind::List => format!("{}=<{}>,...", self.name, self.name),
+		};
+
+		if self.required {
+			usage
+		} else {
+			format!("[{usage}]")
+		}
+	}
+}
+
+impl SubcommandSchema {
+	pub fn usage(&self, path: &str) -> String {
+		let positional = self.arguments.iter().filter(|arg| arg.kind.is_positional());
+		let named = self
+			.arguments
+			.iter()
+			.filter(|arg| !arg.kind.is_positional());
+
+		let mut usage = if path.is_empty() {
+			self.name.to_string()
+		} else {
+			format!("{path} {}", self.name)
+		};
+		for arg in positional.chain(named) {
+			usage.push(' ');
+			usage.push_str(&arg.usage());
+		}
+		usage
+	}
+}
+
+impl CommandsSchema {
+	pub fn names(&self) -> Vec<&'static str> {
+		match self {
+			Self::Categories(categories) => categories.iter().map(|c| c.name).collect(),
+			Self::Subcommands(subcommands) => subcommands.iter().map(|s| s.name).collect(),
+		}
+	}
+}
@@ -0,0 +1,316 @@
+use crate::errors::{ParseCommandError, ParseCommandErrorKind};
+use core::ops::Range;
+use std::borrow::Cow;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Token {
+	pub key: Option<String>,
+	pub value: String,
+	pub span: Range<usize>,
+	pub value_span: Range<usize>,
+}
+
+impl Token {
+	pub fn key_span(&self) -> Range<usize> {
+		match self.key {
+			Some(_) => self.span.start..self.value_span.start - 1,
+			None => self.span.clone(),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Quote {
+	None,
+	Single,
+	Double,
+}
+
+pub fn tokenize(string: &str) -> Result<Vec<Token>, ParseCommandError> {
+	let mut tokens = Vec::new();
+	let mut chars = string.char_indices().peekable();
+
+	loop {
+		while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+		let Some(&(start, _)) = chars.peek() else {
+			break;
+		};
+
+		let mut key = None;
+		let mut value = String::new();
+		let mut value_start = start;
+		let mut end = string.len();
+		let mut quote = Quote::None;
+		let mut quote_start = start;
+		let mut was_quoted = false;
+
+		while let Some((i, c)) = chars.next() {
+			match (quote, c) {
+				(Quote::None, c) if c.is_whitespace() => {
+					end = i;
+					break;
+				}
+				(Quote::None, '\'') => {
+					quote = Quote::Single;
+					quote_start = i;
+					was_quoted = true;
+				}
+				(Quote::None, '"') => {
+					quote = Quote::Double;
+					quote_start = i;
+					was_quoted = true;
+				}
+				(Quote::None, '=') if key.is_none() && !was_quoted && !value.is_empty() => {
+					key = Some(std::mem::take(&mut value));
+					value_start = i + 1;
+				}
+				(Quote::None | Quote::Double, '\\') => match chars.next() {
+					Some((_, escaped)) => value.push(escaped),
+					None => {
+						return Err(ParseCommandErrorKind::TrailingEscape.at(i..string.len()));
+					}
+				},
+				(Quote::Single, '\'') | (Quote::Double, '"') => quote = Quote::None,
+				(_, c) => value.push(c),
+			}
+		}
+
+		if quote != Quote::None {
+			return Err(ParseCommandErrorKind::UnterminatedQuote.at(quote_start..string.len()));
+		}
+
+		tokens.push(Token {
+			key,
+			value,
+			span: start..end,
+			value_span: value_start..end,
+		});
+	}
+
+	Ok(tokens)
+}
+
+pub fn split_list(string: &str) -> Result<Vec<(String, Range<usize>)>, ParseCommandError> {
+	let mut items = Vec::new();
+	if string.is_empty() {
+		return Ok(items);
+	}
+
+	let mut chars = string.char_indices();
+	let mut value = String::new();
+	let mut start = 0;
+	let mut quote = Quote::None;
+	let mut quote_start = 0;
+
+	while let Some((i, c)) = chars.next() {
+		match (quote, c) {
+			(Quote::None, ',') => {
+				items.push((std::mem::take(&mut value), start..i));
+				start = i + 1;
+			}
+			(Quote::None, '\'') => {
+				quote = Quote::Single;
+				quote_start = i;
+			}
+			(Quote::None, '"') => {
+				quote = Quote::Double;
+				quote_start = i;
+			}
+			(Quote::None | Quote::Double, '\\') => match chars.next() {
+				Some((_, escaped)) => value.push(escaped),
+				None => return Err(ParseCommandErrorKind::TrailingEscape.at(i..string.len())),
+			},
+			(Quote::Single, '\'') | (Quote::Double, '"') => quote = Quote::None,
+			(_, c) => value.push(c),
+		}
+	}
+
+	if quote != Quote::None {
+		return Err(ParseCommandErrorKind::UnterminatedQuote.at(quote_start..string.len()));
+	}
+
+	items.push((value, start..string.len()));
+	Ok(items)
+}
+
+pub fn split_word(string: &str) -> (&str, Range<usize>, usize) {
+	let start = string.len() - string.trim_start().len();
+	let end = string[start..]
+		.find(char::is_whitespace)
+		.map_or(string.len(), |i| start + i);
+
+	(&string[start..end], start..end, end)
+}
+
+pub fn quote(value: &str) -> Cow<'_, str> {
+	let needs_quotes = value.is_empty()
+		|| value
+			.chars()
+			.any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | '=' | ',' | ';' | '#'));
+
+	if !needs_quotes {
+		return Cow::Borrowed(value);
+	}
+
+	let mut quoted = String::with_capacity(value.len() + 2);
+	quoted.push('"');
+	for c in value.chars() {
+		if matches!(c, '"' | '\\') {
+			quoted.push('\\');
+		}
+		quoted.push(c);
+	}
+	quoted.push('"');
+	Cow::Owned(quoted)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	type Pair = (Option<String>, String);
+
+	fn tokens(string: &str) -> Result<Vec<Pair>, ParseCommandError> {
+		Ok(tokenize(string)?
+			.into_iter()
+			.map(|token| (token.key, token.value))
+			.collect())
+	}
+
+	fn positional(value: &str) -> Pair {
+		(None, value.to_string())
+	}
+
+	fn named(key: &str, value: &str) -> Pair {
+		(Some(key.to_string()), value.to_string())
+	}
+
+	#[test]
+	fn splits_on_whitespace() -> Result<(), ParseCommandError> {
+		assert_eq!(
+			tokens("  list \t sort=desc  ")?,
+			vec![positional("list"), named("sort", "desc")]
+		);
+		assert_eq!(tokens("")?, vec![]);
+		assert_eq!(tokens("   ")?, vec![]);
+
+		Ok(())
+	}
+
+	#[test]
+	fn handles_quotes() -> Result<(), ParseCommandError> {
+		assert_eq!(
+			tokens(r#"name="Late Night Jazz""#)?,
+			vec![named("name", "Late Night Jazz")]
+		);
+		assert_eq!(
+			tokens("name='Kind of \"Blue\"'")?,
+			vec![named("name", "Kind of \"Blue\"")]
+		);
+		assert_eq!(
+			tokens(r#"path=/music/"Miles Davis"/album"#)?,
+			vec![named("path", "/music/Miles Davis/album")]
+		);
+		assert_eq!(tokens(r#"name="""#)?, vec![named("name", "")]);
+		assert_eq!(tokens(r#""a=b""#)?, vec![positional("a=b")]);
+
+		Ok(())
+	}
+
+	#[test]
+	fn handles_escapes() -> Result<(), ParseCommandError> {
+		assert_eq!(
+			tokens(r"name=Late\ Night\ Jazz")?,
+			vec![named("name", "Late Night Jazz")]
+		);
+		assert_eq!(
+			tokens(r#"name="say \"hi\" \\o/""#)?,
+			vec![named("name", r#"say "hi" \o/"#)]
+		);
+		assert_eq!(
+			tokens(r"name='no \escapes'")?,
+			vec![named("name", r"no \escapes")]
+		);
+		assert_eq!(tokens(r"a\=b")?, vec![positional("a=b")]);
+
+		Ok(())
+	}
+
+	#[test]
+	fn equals_inside_value() -> Result<(), ParseCommandError> {
+		assert_eq!(tokens("query=a=b")?, vec![named("query", "a=b")]);
+		assert_eq!(tokens("=foo")?, vec![positional("=foo")]);
+		assert_eq!(tokens("key=")?, vec![named("key", "")]);
+
+		Ok(())
+	}
+
+	#[test]
+	fn quote_round_trips() -> Result<(), ParseCommandError> {
+		assert_eq!(quote("plain"), "plain");
+		assert_eq!(quote(""), r#""""#);
+		assert_eq!(quote("Late Night Jazz"), r#""Late Night Jazz""#);
+		assert_eq!(quote(r#"say "hi" \o/"#), r#""say \"hi\" \\o/""#);
+
+		for value in ["", "a=b", "a,b", "it's", r#"a "b" \c"#, " \t\n "] {
+			assert_eq!(tokens(&quote(value))?, vec![positional(value)]);
+		}
+
+		Ok(())
+	}
+
+	#[test]
+	fn records_spans() -> Result<(), ParseCommandError> {
+		let tokens = tokenize(r#"  list name="a b""#)?;
+
+		assert_eq!(tokens[0].span, 2..6);
+		assert_eq!(tokens[0].key_span(), 2..6);
+		assert_eq!(tokens[1].span, 7..17);
+		assert_eq!(tokens[1].key_span(), 7..11);
+		assert_eq!(tokens[1].value_span, 12..17);
+
+		Ok(())
+	}
+
+	#[test]
+	fn splits_lists() -> Result<(), ParseCommandError> {
+		assert_eq!(
+			split_list(r#"3,"a, b",'c'\,d"#)?,
+			vec![
+				("3".to_string(), 0..1),
+				("a, b".to_string(), 2..8),
+				("c,d".to_string(), 9..15),
+			]
+		);
+		assert_eq!(split_list("")?, vec![]);
+		assert_eq!(
+			split_list(",")?,
+			vec![(String::new(), 0..0), (String::new(), 1..1)]
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn splits_first_word() {
+		assert_eq!(split_word("  album list"), ("album", 2..7, 7));
+		assert_eq!(split_word("album"), ("album", 0..5, 5));
+		assert_eq!(split_word("   "), ("", 3..3, 3));
+	}
+
+	#[test]
+	fn invalid_input_errors() {
+		assert_eq!(
+			tokenize(r#"name="unterminated"#),
+			Err(ParseCommandErrorKind::UnterminatedQuote.at(5..18))
+		);
+		assert_eq!(
+			tokenize("name='unterminated"),
+			Err(ParseCommandErrorKind::UnterminatedQuote.at(5..18))
+		);
+		assert_eq!(
+			tokenize(r"name=trailing\"),
+			Err(ParseCommandErrorKind::TrailingEscape.at(13..14))
+		);
+	}
+}
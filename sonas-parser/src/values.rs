@@ -0,0 +1,344 @@
+use crate::{errors::InvalidValueError, schema::ArgumentValues};
+use core::{fmt, str::FromStr, time::Duration};
+
+pub type TimeOffset = Relative<Timestamp>;
+
+pub trait Adjust: Copy {
+	fn increase(self, by: Self) -> Self;
+	fn decrease(self, by: Self) -> Self;
+}
+
+macro_rules! impl_adjust {
+	($($ty:ty),*) => {
+		$(impl Adjust for $ty {
+			fn increase(self, by: Self) -> Self {
+				self.saturating_add(by)
+			}
+
+			fn decrease(self, by: Self) -> Self {
+				self.saturating_sub(by)
+			}
+		})*
+	};
+}
+
+impl_adjust!(
+	u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, Duration
+);
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Relative<T> {
+	Absolute(T),
+	Increase(T),
+	Decrease(T),
+}
+
+impl<T: Adjust> Relative<T> {
+	pub fn apply(self, current: T) -> T {
+		match self {
+			Self::Absolute(value) => value,
+			Self::Increase(value) => current.increase(value),
+			Self::Decrease(value) => current.decrease(value),
+		}
+	}
+}
+
+impl<T: FromStr<Err: fmt::Display>> FromStr for Relative<T> {
+	type Err = InvalidValueError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (relative, value): (fn(T) -> Self, _) = if let Some(value) = s.strip_prefix('+') {
+			(Self::Increase, value)
+		} else if let Some(value) = s.strip_prefix('-') {
+			(Self::Decrease, value)
+		} else {
+			(Self::Absolute, s)
+		};
+
+		if value.is_empty() {
+			return Err(InvalidValueError::MissingValue(s.to_string()));
+		}
+
+		value
+			.parse()
+			.map(relative)
+			.map_err(|error: T::Err| InvalidValueError::Invalid {
+				value: s.to_string(),
+				reason: error.to_string(),
+			})
+	}
+}
+
+impl<T: fmt::Display> fmt::Display for Relative<T> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Absolute(value) => write!(f, "{value}"),
+			Self::Increase(value) => write!(f, "+{value}"),
+			Self::Decrease(value) => write!(f, "-{value}"),
+		}
+	}
+}
+
+impl<T> ArgumentValues for Relative<T> {}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord, Default)]
+pub struct Percentage(u8);
+
+impl Percentage {
+	pub const MAX: Self = Self(100);
+
+	pub fn new(value: u8) -> Option<Self> {
+		(value <= 100).then_some(Self(value))
+	}
+
+	pub fn get(self) -> u8 {
+		self.0
+	}
+
+	pub fn fraction(self) -> f32 {
+		f32::from(self.0) / 100.
+	}
+}
+
+impl Adjust for Percentage {
+	fn increase(self, by: Self) -> Self {
+		Self(self.0.saturating_add(by.0).min(100))
+	}
+
+	fn decrease(self, by: Self) -> Self {
+		Self(self.0.saturating_sub(by.0))
+	}
+}
+
+impl FromStr for Percentage {
+	type Err = InvalidValueError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let value = s
+			.strip_suffix('%')
+			.and_then(|value| value.parse::<u16>().ok())
+			.ok_or_else(|| InvalidValueError::NotAPercentage(s.to_string()))?;
+
+		u8::try_from(value)
+			.ok()
+			.and_then(Self::new)
+			.ok_or_else(|| InvalidValueError::PercentageOutOfRange(s.to_string()))
+	}
+}
+
+impl fmt::Display for Percentage {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}%", self.0)
+	}
+}
+
+impl ArgumentValues for Percentage {}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord, Default)]
+pub struct Timestamp(pub Duration);
+
+impl Adjust for Timestamp {
+	fn increase(self, by: Self) -> Self {
+		Self(self.0.saturating_add(by.0))
+	}
+
+	fn decrease(self, by: Self) -> Self {
+		Self(self.0.saturating_sub(by.0))
+	}
+}
+
+impl FromStr for Timestamp {
+	type Err = InvalidValueError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let duration = if s.contains(':') {
+			parse_clock(s)
+		} else if s.ends_with(|c: char| c.is_ascii_alphabetic()) {
+			parse_units(s)
+		} else {
+			parse_seconds(s)
+		};
+
+		duration
+			.map(Self)
+			.ok_or_else(|| InvalidValueError::InvalidTime(s.to_string()))
+	}
+}
+
+impl fmt::Display for Timestamp {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let seconds = self.0.as_secs();
+		let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+		if hours > 0 {
+			write!(f, "{hours}:{minutes:02}:{seconds:02}")?;
+		} else {
+			write!(f, "{minutes}:{seconds:02}")?;
+		}
+
+		let nanos = self.0.subsec_nanos();
+		if nanos > 0 {
+			write!(f, ".{}", format!("{nanos:09}").trim_end_matches('0'))?;
+		}
+		Ok(())
+	}
+}
+
+impl ArgumentValues for Timestamp {}
+
+fn parse_seconds(s: &str) -> Option<Duration> {
+	let (seconds, fraction) = s.split_once('.').unwrap_or((s, ""));
+	if seconds.is_empty() || fraction.len() > 9 {
+		return None;
+	}
+	if !(seconds.chars().chain(fraction.chars())).all(|c| c.is_ascii_digit()) {
+		return None;
+	}
+
+	let nanos = if fraction.is_empty() {
+		0
+	} else {
+		format!("{fraction:0<9}").parse().ok()?
+	};
+	Some(Duration::new(seconds.parse().ok()?, nanos))
+}
+
+fn parse_clock(s: &str) -> Option<Duration> {
+	let parts = s.split(':').collect::<Vec<_>>();
+	let (hours, minutes, seconds) = match parts[..] {
+		[minutes, seconds] => ("0", minutes, seconds),
+		[hours, minutes, seconds] => (hours, minutes, seconds),
+		_ => return None,
+	};
+
+	let hours = hours.parse::<u64>().ok()?;
+	let minutes = minutes.parse::<u64>().ok()?;
+	let seconds = parse_seconds(seconds).filter(|seconds| seconds.as_secs() < 60)?;
+	if parts.len() == 3 && minutes >= 60 {
+		return None;
+	}
+
+	let whole = hours
+		.checked_mul(3600)?
+		.checked_add(minutes.checked_mul(60)?)?;
+	Duration::from_secs(whole).checked_add(seconds)
+}
+
+fn parse_units(s: &str) -> Option<Duration> {
+	let mut total = Duration::ZERO;
+	let mut rest = s;
+
+	while !rest.is_empty() {
+		let number_end = rest.find(|c: char| c.is_ascii_alphabetic())?;
+		let unit_end = rest[number_end..]
+			.find(|c: char| !c.is_ascii_alphabetic())
+			.map_or(rest.len(), |i| number_end + i);
+
+		let value = parse_seconds(&rest[..number_end])?;
+		let scale = match &rest[number_end..unit_end] {
+			"h" => 3600,
+			"m" => 60,
+			"s" => 1,
+			_ => return None,
+		};
+		total = total.checked_add(value.checked_mul(scale)?)?;
+		rest = &rest[unit_end..];
+	}
+
+	Some(total)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parses_relative_numbers() {
+		assert_eq!("+5".parse(), Ok(Relative::Increase(5u8)));
+		assert_eq!("-5".parse(), Ok(Relative::Decrease(5u8)));
+		assert_eq!("5".parse(), Ok(Relative::Absolute(5u8)));
+		assert_eq!(
+			"+".parse::<Relative<u8>>(),
+			Err(InvalidValueError::MissingValue("+".to_string()))
+		);
+		assert_eq!(
+			"+x".parse::<Relative<u8>>().unwrap_err().to_string(),
+			"invalid value '+x': invalid digit found in string"
+		);
+
+		assert_eq!(Relative::Increase(5u8).apply(253), 255);
+		assert_eq!(Relative::Decrease(5u8).apply(3), 0);
+		assert_eq!(Relative::Absolute(5u8).apply(3), 5);
+	}
+
+	#[test]
+	fn parses_percentages() {
+		assert_eq!("50%".parse::<Percentage>().map(Percentage::get), Ok(50));
+		assert_eq!(
+			"50".parse::<Percentage>(),
+			Err(InvalidValueError::NotAPercentage("50".to_string()))
+		);
+		assert_eq!(
+			"101%".parse::<Percentage>(),
+			Err(InvalidValueError::PercentageOutOfRange("101%".to_string()))
+		);
+		assert_eq!(
+			"+10%"
+				.parse::<Relative<Percentage>>()
+				.map(|relative| relative.apply(Percentage(95))),
+			Ok(Percentage(100))
+		);
+	}
+
+	#[test]
+	fn parses_time_offsets() {
+		let seconds = |s: u64| Timestamp(Duration::from_secs(s));
+
+		assert_eq!("1:23".parse(), Ok(TimeOffset::Absolute(seconds(83))));
+		assert_eq!("1:02:03".parse(), Ok(TimeOffset::Absolute(seconds(3723))));
+		assert_eq!("90".parse(), Ok(TimeOffset::Absolute(seconds(90))));
+		assert_eq!("+10s".parse(), Ok(TimeOffset::Increase(seconds(10))));
+		assert_eq!("-1m30s".parse(), Ok(TimeOffset::Decrease(seconds(90))));
+		assert_eq!(
+			"1.5s".parse(),
+			Ok(TimeOffset::Absolute(Timestamp(Duration::from_millis(1500))))
+		);
+
+		let overflowing = [
+			"18446744073709551615:00",
+			"5124095576030432:00:00",
+			"307445734561825860:59.5",
+		];
+		for invalid in ["1:60", "1:2:3:4", "10x", "1m30", "s", "", "1:-1"]
+			.into_iter()
+			.chain(overflowing)
+		{
+			assert_eq!(
+				invalid.parse::<Timestamp>(),
+				Err(InvalidValueError::InvalidTime(invalid.to_string())),
+				"{invalid}"
+			);
+		}
+
+		assert_eq!(
+			TimeOffset::Decrease(seconds(10)).apply(seconds(5)),
+			seconds(0)
+		);
+	}
+
+	#[test]
+	fn display_round_trips() {
+		for value in ["+5", "-5", "5"] {
+			assert_eq!(value.parse::<Relative<u8>>().unwrap().to_string(), value);
+		}
+		for value in ["0%", "+5%", "-100%"] {
+			assert_eq!(
+				value.parse::<Relative<Percentage>>().unwrap().to_string(),
+				value
+			);
+		}
+		for value in ["0:00", "1:23", "+1:02:03", "-0:01.5", "+0:00.000000001"] {
+			assert_eq!(value.parse::<TimeOffset>().unwrap().to_string(), value);
+		}
+	}
+}
@@ -0,0 +1,309 @@
+use crate::{Command, protocol::PlainVerb, script::Script};
+use ::config::{Config, ConfigError, File};
+use serde::Deserialize;
+use sonas_parser::{ParseCommandError, schema::CommandSchema, tokenizer};
+use std::{collections::HashMap, path::Path};
+use thiserror::Error;
+
+/// Aliases may call each other several times, so an expansion can grow
+/// exponentially without a cycle
+const MAX_EXPANDED_COMMANDS: usize = 1000;
+
+#[derive(Debug, Clone, Eq, PartialEq, Error)]
+pub enum AliasError {
+	#[error("alias cycle detected: {}", .0.join(" -> "))]
+	Cycle(Vec<String>),
+	#[error("alias '{0}' shadows a built-in command")]
+	ShadowsCommand(String),
+	#[error("alias '{alias}' expects parameter ${index}")]
+	MissingParameter { alias: String, index: usize },
+	#[error("alias '{alias}' takes {expected} parameter(s) but {given} were given")]
+	UnexpectedParameter {
+		alias: String,
+		expected: usize,
+		given: usize,
+	},
+	#[error("alias '{0}' expands to more than {MAX_EXPANDED_COMMANDS} commands")]
+	TooManyCommands(String),
+	#[error("invalid alias arguments: {0}")]
+	InvalidArguments(Box<ParseCommandError>),
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq, Deserialize)]
+#[serde(transparent)]
+pub struct AliasTable(HashMap<String, String>);
+
+impl AliasTable {
+	pub fn load(path: &Path) -> Result<Self, ConfigError> {
+		let path = path
+			.to_str()
+			.ok_or_else(|| ConfigError::Message("invalid config path".to_string()))?;
+		let config = Config::builder()
+			.add_source(File::with_name(path).required(false))
+			.build()?;
+
+		match config.get::<Self>("aliases") {
+			Err(ConfigError::NotFound(_)) => Ok(Self::default()),
+			aliases => aliases,
+		}
+	}
+
+	pub fn contains(&self, name: &str) -> bool {
+		self.0.contains_key(name)
+	}
+
+	pub fn validate(&self) -> Result<(), AliasError> {
+		let commands = Command::SCHEMA.names();
+		if let Some(name) = self
+			.0
+			.keys()
+			.find(|name| commands.contains(&name.as_str()) || PlainVerb::from_name(name).is_some())
+		{
+			return Err(AliasError::ShadowsCommand(name.clone()));
+		}
+
+		let mut done = Vec::new();
+		let mut names = self.0.keys().collect::<Vec<_>>();
+		names.sort();
+		for name in names {
+			self.check_cycles(name, &mut Vec::new(), &mut done)?;
+		}
+		Ok(())
+	}
+
+	fn check_cycles<'a>(
+		&'a self,
+		name: &'a str,
+		stack: &mut Vec<&'a str>,
+		done: &mut Vec<&'a str>,
+	) -> Result<(), AliasError> {
+		if done.contains(&name) {
+			return Ok(());
+		}
+		if let Some(start) = stack.iter().position(|&entry| entry == name) {
+			let mut cycle = stack[start..]
+				.iter()
+				.map(|entry| entry.to_string())
+				.collect::<Vec<_>>();
+			cycle.push(name.to_string());
+			return Err(AliasError::Cycle(cycle));
+		}
+		let Some(body) = self.0.get(name) else {
+			return Ok(());
+		};
+
+		stack.push(name);
+		for statement in &Script::parse(body).statements {
+			let (word, _, _) = tokenizer::split_word(&statement.text);
+			if let Some((referenced, _)) = self.0.get_key_value(word) {
+				self.check_cycles(referenced, stack, done)?;
+			}
+		}
+		stack.pop();
+		done.push(name);
+
+		Ok(())
+	}
+
+	pub fn expand(&self, input: &str) -> Result<Vec<String>, AliasError> {
+		let mut commands = Vec::new();
+		self.expand_into(input, &mut Vec::new(), &mut commands)?;
+		Ok(commands)
+	}
+
+	fn expand_into(
+		&self,
+		input: &str,
+		stack: &mut Vec<String>,
+		commands: &mut Vec<String>,
+	) -> Result<(), AliasError> {
+		let (name, _, offset) = tokenizer::split_word(input);
+		let Some(body) = self.0.get(name) else {
+			if commands.len() == MAX_EXPANDED_COMMANDS {
+				return Err(AliasError::TooManyCommands(stack[0].clone()));
+			}
+			commands.push(input.to_string());
+			return Ok(());
+		};
+		if stack.iter().any(|entry| entry == name) {
+			let mut cycle = stack.clone();
+			cycle.push(name.to_string());
+			return Err(AliasError::Cycle(cycle));
+		}
+
+		let rest = &input[offset..];
+		let arguments = tokenizer::tokenize(rest)
+			.map_err(|error| AliasError::InvalidArguments(Box::new(error)))?
+			.into_iter()
+			.map(|token| &rest[token.span])
+			.collect::<Vec<_>>();
+		let body = substitute(name, body, &arguments)?;
+
+		stack.push(name.to_string());
+		for statement in Script::parse(&body).statements {
+			self.expand_into(&statement.text, stack, commands)?;
+		}
+		stack.pop();
+
+		Ok(())
+	}
+}
+
+fn substitute(alias: &str, body: &str, arguments: &[&str]) -> Result<String, AliasError> {
+	let mut result = String::with_capacity(body.len());
+	let mut chars = body.chars().peekable();
+	let mut used = 0;
+	let mut variadic = false;
+
+	while let Some(c) = chars.next() {
+		if c != '$' {
+			result.push(c);
+			continue;
+		}
+
+		match chars.peek() {
+			Some('$') => {
+				chars.next();
+				result.push('$');
+			}
+			Some('@') => {
+				chars.next();
+				variadic = true;
+				result.push_str(&arguments.join(" "));
+			}
+			Some(digit) if digit.is_ascii_digit() => {
+				let mut index = 0;
+				while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+					index = index * 10 + digit.to_digit(10).unwrap_or_default() as usize;
+				}
+				let argument = index
+					.checked_sub(1)
+					.and_then(|i| arguments.get(i))
+					.ok_or_else(|| AliasError::MissingParameter {
+						alias: alias.to_string(),
+						index,
+					})?;
+				used = used.max(index);
+				result.push_str(argument);
+			}
+			_ => result.push(c),
+		}
+	}
+
+	if !variadic && arguments.len() > used {
+		return Err(AliasError::UnexpectedParameter {
+			alias: alias.to_string(),
+			expected: used,
+			given: arguments.len(),
+		});
+	}
+
+	Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn table(aliases: &[(&str, &str)]) -> AliasTable {
+		AliasTable(
+			aliases
+				.iter()
+				.map(|(name, body)| (name.to_string(), body.to_string()))
+				.collect(),
+		)
+	}
+
+	#[test]
+	fn expands_aliases() -> Result<(), AliasError> {
+		let aliases = table(&[
+			("oldest", "album list sort=asc"),
+			("tracks", "album list-tracks $1"),
+			("both", "oldest; tracks $1"),
+			("each", "album list-tracks $@"),
+		]);
+
+		assert_eq!(aliases.expand("album list")?, vec!["album list"]);
+		assert_eq!(aliases.expand("oldest")?, vec!["album list sort=asc"]);
+		assert_eq!(aliases.expand("tracks 5")?, vec!["album list-tracks 5"]);
+		assert_eq!(
+			aliases.expand("both 'a b'")?,
+			vec!["album list sort=asc", "album list-tracks 'a b'"]
+		);
+		assert_eq!(aliases.expand("each 1 2")?, vec!["album list-tracks 1 2"]);
+
+		Ok(())
+	}
+
+	#[test]
+	fn parameter_errors() {
+		let aliases = table(&[("tracks", "album list-tracks $1"), ("oldest", "album list")]);
+
+		assert_eq!(
+			aliases.expand("tracks"),
+			Err(AliasError::MissingParameter {
+				alias: "tracks".to_string(),
+				index: 1,
+			})
+		);
+		assert_eq!(
+			aliases.expand("oldest 3"),
+			Err(AliasError::UnexpectedParameter {
+				alias: "oldest".to_string(),
+				expected: 0,
+				given: 1,
+			})
+		);
+	}
+
+	#[test]
+	fn detects_cycles() {
+		let aliases = table(&[("a", "b"), ("b", "album list; c 1"), ("c", "a")]);
+
+		assert_eq!(
+			aliases.validate(),
+			Err(AliasError::Cycle(
+				["a", "b", "c", "a"].map(str::to_string).to_vec()
+			))
+		);
+
+		let aliases = table(&[("a", "b; b"), ("b", "album list")]);
+		assert_eq!(aliases.validate(), Ok(()));
+	}
+
+	#[test]
+	fn limits_the_expanded_commands() {
+		// every alias runs the next one twice, so `a` runs 2^12 commands
+		let mut aliases = (b'a'..b'l')
+			.map(|name| {
+				let next = char::from(name + 1);
+				(char::from(name).to_string(), format!("{next}; {next}"))
+			})
+			.collect::<Vec<_>>();
+		aliases.push(("l".to_string(), "player next; player next".to_string()));
+		let aliases = AliasTable(aliases.into_iter().collect());
+
+		assert_eq!(aliases.validate(), Ok(()));
+		assert_eq!(aliases.expand("e").map(|commands| commands.len()), Ok(256));
+		assert_eq!(
+			aliases.expand("a"),
+			Err(AliasError::TooManyCommands("a".to_string()))
+		);
+	}
+
+	#[test]
+	fn rejects_shadowing() {
+		let aliases = table(&[("album", "album list")]);
+
+		assert_eq!(
+			aliases.validate(),
+			Err(AliasError::ShadowsCommand("album".to_string()))
+		);
+
+		let aliases = table(&[("subscribe", "album list")]);
+		assert_eq!(
+			aliases.validate(),
+			Err(AliasError::ShadowsCommand("subscribe".to_string()))
+		);
+	}
+}
@@ -1,47 +1,148 @@
-use sonas_macros::{CommandCategory, Subcommand};
-use std::str::FromStr;
+use crate::player::RepeatMode;
+use derive_more::From;
+use sonas_macros::{CommandCategory, CommandValue, Subcommand};
+use sonas_parser::{ParseCommandError, Percentage, Relative, TimeOffset, help};
 
-#[derive(Debug, Clone, Eq, PartialEq, CommandCategory)]
+#[derive(Debug, Clone, Eq, PartialEq, From, CommandCategory)]
 pub enum Command {
+	/// Browse the albums in the library
 	Album(AlbumCommand),
+	/// Control playback
+	Player(PlayerCommand),
+	/// Edit the tracks that are played next
+	Queue(QueueCommand),
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Subcommand)]
 pub enum AlbumCommand {
+	/// List all albums in the library
 	List {
+		/// Order in which the albums are listed
 		#[default(SortDirection::Descending)]
 		sort: SortDirection,
 	},
+	/// List the tracks of an album
 	ListTracks {
+		/// Id of the album
+		#[positional]
 		id: usize,
 	},
+	/// List the tracks that match all of the given filters
+	Search {
+		/// Text in the artist, album title or track title
+		any: Option<String>,
+		artist: Option<String>,
+		album: Option<String>,
+		title: Option<String>,
+		year: Option<u16>,
+		/// Match whole values with the same case instead of parts of them
+		#[fallback_to_default]
+		exact: bool,
+	},
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Subcommand)]
+pub enum PlayerCommand {
+	/// Show the playback state and the current track
+	Status,
+	/// Start playing, from the given queue position if there is one
+	Play {
+		/// Position in the queue, starting at 0
+		#[positional]
+		position: Option<usize>,
+	},
+	/// Pause playback
+	Pause,
+	/// Pause when playing and play otherwise
+	Toggle,
+	/// Stop playback and rewind the current track
+	Stop,
+	/// Skip to the next track in the queue
+	Next,
+	/// Go back to the previous track in the queue
+	Previous,
+	/// Jump to a time in the current track
+	Seek {
+		/// Time to jump to, or an offset like +10s
+		#[positional]
+		time: TimeOffset,
+	},
+	/// Change the volume
+	Volume {
+		/// Volume to set, or an offset like -5%
+		#[positional]
+		level: Relative<Percentage>,
+	},
+	/// Play the queue in random order
+	Shuffle {
+		#[positional]
+		enabled: bool,
+	},
+	/// Choose what to play after the current track ends
+	Repeat {
+		#[positional]
+		mode: RepeatMode,
+	},
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Subcommand)]
+pub enum QueueCommand {
+	/// List the tracks in the queue
+	List,
+	/// Append an album, or one of its tracks, to the queue
+	Add {
+		/// Id of the album
+		#[positional]
+		album: usize,
+		/// Number of the track, the whole album when left out
+		#[positional]
+		track: Option<u32>,
+	},
+	/// Remove tracks from the queue
+	Delete {
+		/// Position of the first track to remove, starting at 0
+		#[positional]
+		position: usize,
+		/// Number of tracks to remove
+		#[default(1)]
+		count: usize,
+	},
+	/// Move a track to another position in the queue
+	Move {
+		#[positional]
+		from: usize,
+		#[positional]
+		to: usize,
+	},
+	/// Remove all tracks from the queue
+	Clear,
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Default)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default, CommandValue)]
 pub enum SortDirection {
 	#[default]
+	#[alias("a", "asc")]
 	Ascending,
+	#[alias("d", "desc")]
 	Descending,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
-pub struct UnknownSortDirectionError(String);
-
-impl FromStr for SortDirection {
-	type Err = UnknownSortDirectionError;
+impl Command {
+	pub fn help(path: &str) -> Result<String, ParseCommandError> {
+		help::help::<Self>(path)
+	}
 
-	fn from_str(s: &str) -> Result<Self, Self::Err> {
-		match s.to_lowercase() {
-			x if x == "a" || x == "asc" || x == "ascending" => Ok(Self::Ascending),
-			x if x == "d" || x == "desc" || x == "descending" => Ok(Self::Descending),
-			other => Err(UnknownSortDirectionError(other)),
-		}
+	pub fn usage(input: &str) -> String {
+		help::usage::<Self>(input)
 	}
 }
 
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use core::{fmt, time::Duration};
+	use proptest::prelude::*;
+	use sonas_parser::{ParseCommandErrorKind, Timestamp, schema::CommandSchema};
 
 	#[test]
 	fn it_works() {
@@ -63,5 +164,419 @@ mod tests {
 
 		let command = "album list-tracks id=5".parse::<Command>().unwrap();
 		assert_eq!(command, Command::Album(AlbumCommand::ListTracks { id: 5 }));
+
+		let command = "album list-tracks 5".parse::<Command>().unwrap();
+		assert_eq!(command, Command::Album(AlbumCommand::ListTracks { id: 5 }));
+	}
+
+	#[derive(Debug, Clone, Eq, PartialEq, Subcommand)]
+	enum PositionalCommand {
+		Add {
+			#[positional]
+			id: usize,
+			#[positional(variadic)]
+			paths: Vec<String>,
+			#[fallback_to_default]
+			force: bool,
+		},
+	}
+
+	#[test]
+	fn positional_arguments() {
+		let command = "add 3 a.flac 'b c.flac' force=true"
+			.parse::<PositionalCommand>()
+			.unwrap();
+		assert_eq!(
+			command,
+			PositionalCommand::Add {
+				id: 3,
+				paths: vec!["a.flac".to_string(), "b c.flac".to_string()],
+				force: true,
+			}
+		);
+
+		let command = "add id=3".parse::<PositionalCommand>().unwrap();
+		assert_eq!(
+			command,
+			PositionalCommand::Add {
+				id: 3,
+				paths: vec![],
+				force: false,
+			}
+		);
+
+		let command = "add force=true 3".parse::<PositionalCommand>();
+		assert_eq!(
+			command,
+			Err(ParseCommandErrorKind::PositionalAfterNamed("3".to_string())
+				.at(15..16)
+				.within("add"))
+		);
+
+		let command = "add".parse::<PositionalCommand>();
+		assert_eq!(
+			command,
+			Err(ParseCommandErrorKind::MissingArgument("id".to_string())
+				.at(3..3)
+				.within("add"))
+		);
+	}
+
+	#[derive(Debug, Clone, Eq, PartialEq, Subcommand)]
+	enum ListCommand {
+		Set {
+			#[positional]
+			id: usize,
+			genre: Vec<String>,
+			ids: Vec<usize>,
+		},
+	}
+
+	#[test]
+	fn list_arguments() {
+		let command = "set 1 ids=3,7,9 genre=Jazz genre='Soul, Funk'".parse::<ListCommand>();
+		assert_eq!(
+			command,
+			Ok(ListCommand::Set {
+				id: 1,
+				genre: vec!["Jazz".to_string(), "Soul, Funk".to_string()],
+				ids: vec![3, 7, 9],
+			})
+		);
+		assert_eq!(
+			command.unwrap().to_string(),
+			r#"set 1 genre=Jazz,"Soul, Funk" ids=3,7,9"#
+		);
+
+		let command = "set 1".parse::<ListCommand>();
+		assert_eq!(
+			command,
+			Ok(ListCommand::Set {
+				id: 1,
+				genre: vec![],
+				ids: vec![],
+			})
+		);
+
+		let command = "set 1 ids=3,x".parse::<ListCommand>();
+		assert_eq!(command.map_err(|error| error.span), Err(12..13));
+
+		let help = help::help::<ListCommand>("set").unwrap();
+		assert!(help.contains("Usage: set <id> [genre=<genre>,...] [ids=<ids>,...]"));
+	}
+
+	#[test]
+	fn quoted_arguments() {
+		let command = "album\tlist  sort='asc'".parse::<Command>().unwrap();
+		assert_eq!(
+			command,
+			Command::Album(AlbumCommand::List {
+				sort: SortDirection::Ascending
+			})
+		);
+
+		let command = r#"album list "sort=asc""#.parse::<Command>();
+		assert_eq!(
+			command,
+			Err(
+				ParseCommandErrorKind::UnexpectedPositional("sort=asc".to_string())
+					.at(11..21)
+					.within_path(&["album", "list"])
+			)
+		);
+	}
+
+	#[test]
+	fn help() {
+		let help = Command::help("album list").unwrap();
+		assert!(help.starts_with("List all albums in the library\n"));
+		assert!(help.contains("Usage: album list [sort=<sort>]"));
+		assert!(help.contains("[default: SortDirection::Descending]"));
+
+		let help = Command::help("album").unwrap();
+		assert!(help.contains("list-tracks    List the tracks of an album"));
+
+		assert_eq!(
+			Command::usage("album list-tracks"),
+			Command::help("album list-tracks").unwrap()
+		);
+	}
+
+	#[test]
+	fn schema_serialises_to_json() {
+		let json = serde_json::to_value(Command::SCHEMA).unwrap();
+		let album = &json["categories"][0];
+		assert_eq!(album["name"], "album");
+		assert_eq!(album["doc"], "Browse the albums in the library");
+
+		let list = &album["commands"]["subcommands"][0];
+		assert_eq!(list["name"], "list");
+		assert_eq!(
+			list["arguments"][0],
+			serde_json::json!({
+				"name": "sort",
+				"doc": "Order in which the albums are listed",
+				"ty": "SortDirection",
+				"values": ["ascending", "descending"],
+				"default": "SortDirection::Descending",
+				"required": false,
+				"kind": "named",
+			})
+		);
+
+		let list_tracks = &album["commands"]["subcommands"][1];
+		assert_eq!(list_tracks["arguments"][0]["kind"], "positional");
+		assert_eq!(list_tracks["arguments"][0]["required"], true);
+	}
+
+	#[test]
+	fn command_value() {
+		assert_eq!("ascending".parse(), Ok(SortDirection::Ascending));
+		assert_eq!("ASC".parse(), Ok(SortDirection::Ascending));
+		assert_eq!("a".parse(), Ok(SortDirection::Ascending));
+		assert_eq!("Desc".parse(), Ok(SortDirection::Descending));
+		assert_eq!(SortDirection::Descending.to_string(), "descending");
+		assert_eq!(
+			"sideways".parse::<SortDirection>().unwrap_err().to_string(),
+			"unknown value 'sideways', expected one of: ascending, descending"
+		);
+
+		let command = "album list sort=sideways".parse::<Command>();
+		assert_eq!(
+			command,
+			Err(ParseCommandErrorKind::InvalidArgument {
+				name: "sort".to_string(),
+				reason: "unknown value 'sideways', expected one of: ascending, descending"
+					.to_string(),
+			}
+			.at(16..24)
+			.within_path(&["album", "list"]))
+		);
+	}
+
+	#[test]
+	fn errors_have_spans_and_suggestions() {
+		let input = "albm list";
+		let error = input.parse::<Command>().unwrap_err();
+		assert_eq!(
+			error.diagnostic(input),
+			"error: unknown command category 'albm'\n  \
+			| albm list\n  \
+			| ^^^^ did you mean 'album'?\n"
+		);
+
+		let input = "  album lsit-tracks 5";
+		let error = input.parse::<Command>().unwrap_err();
+		assert_eq!(error.span, 8..19);
+		assert_eq!(error.suggestion.as_deref(), Some("list-tracks"));
+
+		let input = "album list srot=asc";
+		let error = input.parse::<Command>().unwrap_err();
+		assert_eq!(error.span, 11..15);
+		assert_eq!(error.suggestion.as_deref(), Some("sort"));
+
+		let input = "album";
+		let error = input.parse::<Command>().unwrap_err();
+		assert_eq!(
+			error,
+			ParseCommandErrorKind::NoSubcommand.at(5..5).within("album")
+		);
+
+		let input = "album list-tracks";
+		let error = input.parse::<Command>().unwrap_err();
+		assert_eq!(
+			error,
+			ParseCommandErrorKind::MissingArgument("id".to_string())
+				.at(17..17)
+				.within_path(&["album", "list-tracks"])
+		);
+	}
+
+	#[derive(Debug, Clone, Eq, PartialEq, CommandCategory)]
+	enum NestedCommand {
+		/// Browse the library
+		Library(LibraryCommand),
+	}
+
+	#[derive(Debug, Clone, Eq, PartialEq, CommandCategory)]
+	enum LibraryCommand {
+		/// Browse the albums in the library
+		Album(AlbumCommand),
+	}
+
+	#[test]
+	fn nested_categories() {
+		let command = "library album list-tracks 5".parse::<NestedCommand>();
+		assert_eq!(
+			command,
+			Ok(NestedCommand::Library(LibraryCommand::Album(
+				AlbumCommand::ListTracks { id: 5 }
+			)))
+		);
+		assert_eq!(command.unwrap().to_string(), "library album list-tracks 5");
+
+		let input = "library album list-tracks id=x";
+		let error = input.parse::<NestedCommand>().unwrap_err();
+		assert_eq!(error.span, 29..30);
+		assert_eq!(error.path, ["library", "album", "list-tracks"]);
+		assert!(
+			error
+				.to_string()
+				.ends_with("(in 'library album list-tracks')")
+		);
+
+		let error = "library albm list".parse::<NestedCommand>().unwrap_err();
+		assert_eq!(
+			error,
+			ParseCommandErrorKind::UnknownCategory("albm".to_string())
+				.at(8..12)
+				.with_suggestion(Some("album"))
+				.within("library")
+		);
+
+		let help = help::help::<NestedCommand>("library album list").unwrap();
+		assert!(help.contains("Usage: library album list [sort=<sort>]"));
+		let help = help::help::<NestedCommand>("library").unwrap();
+		assert!(help.contains("Usage: library <category> <subcommand> [arguments...]"));
+	}
+
+	#[test]
+	fn display() {
+		let command = Command::Album(AlbumCommand::List {
+			sort: SortDirection::Ascending,
+		});
+		assert_eq!(command.to_string(), "album list sort=ascending");
+
+		let command = Command::Album(AlbumCommand::ListTracks { id: 5 });
+		assert_eq!(command.to_string(), "album list-tracks 5");
+
+		let command = PositionalCommand::Add {
+			id: 3,
+			paths: vec!["a.flac".to_string(), "b c.flac".to_string()],
+			force: false,
+		};
+		assert_eq!(
+			command.to_string(),
+			r#"add 3 a.flac "b c.flac" force=false"#
+		);
+	}
+
+	fn sort_direction() -> impl Strategy<Value = SortDirection> {
+		prop_oneof![
+			Just(SortDirection::Ascending),
+			Just(SortDirection::Descending)
+		]
+	}
+
+	fn repeat_mode() -> impl Strategy<Value = RepeatMode> {
+		prop_oneof![
+			Just(RepeatMode::Off),
+			Just(RepeatMode::One),
+			Just(RepeatMode::All)
+		]
+	}
+
+	fn relative<T: Clone + fmt::Debug>(
+		value: impl Strategy<Value = T> + Clone,
+	) -> impl Strategy<Value = Relative<T>> {
+		prop_oneof![
+			value.clone().prop_map(Relative::Absolute),
+			value.clone().prop_map(Relative::Increase),
+			value.prop_map(Relative::Decrease),
+		]
+	}
+
+	fn timestamp() -> impl Strategy<Value = Timestamp> + Clone {
+		(any::<u64>(), 0..1_000_000_000u32)
+			.prop_map(|(seconds, nanos)| Timestamp(Duration::new(seconds, nanos)))
+	}
+
+	fn percentage() -> impl Strategy<Value = Percentage> + Clone {
+		(0..=100u8).prop_map(|value| Percentage::new(value).unwrap())
+	}
+
+	fn album_command() -> impl Strategy<Value = AlbumCommand> {
+		let search = (
+			any::<[Option<String>; 4]>(),
+			any::<Option<u16>>(),
+			any::<bool>(),
+		);
+		prop_oneof![
+			sort_direction().prop_map(|sort| AlbumCommand::List { sort }),
+			any::<usize>().prop_map(|id| AlbumCommand::ListTracks { id }),
+			search.prop_map(|([any, artist, album, title], year, exact)| {
+				AlbumCommand::Search {
+					any,
+					artist,
+					album,
+					title,
+					year,
+					exact,
+				}
+			}),
+		]
+	}
+
+	fn player_command() -> impl Strategy<Value = PlayerCommand> {
+		prop_oneof![
+			Just(PlayerCommand::Status),
+			any::<Option<usize>>().prop_map(|position| PlayerCommand::Play { position }),
+			Just(PlayerCommand::Pause),
+			Just(PlayerCommand::Toggle),
+			Just(PlayerCommand::Stop),
+			Just(PlayerCommand::Next),
+			Just(PlayerCommand::Previous),
+			relative(timestamp()).prop_map(|time| PlayerCommand::Seek { time }),
+			relative(percentage()).prop_map(|level| PlayerCommand::Volume { level }),
+			any::<bool>().prop_map(|enabled| PlayerCommand::Shuffle { enabled }),
+			repeat_mode().prop_map(|mode| PlayerCommand::Repeat { mode }),
+		]
+	}
+
+	fn queue_command() -> impl Strategy<Value = QueueCommand> {
+		prop_oneof![
+			Just(QueueCommand::List),
+			(any::<usize>(), any::<Option<u32>>())
+				.prop_map(|(album, track)| QueueCommand::Add { album, track }),
+			(any::<usize>(), any::<usize>())
+				.prop_map(|(position, count)| QueueCommand::Delete { position, count }),
+			(any::<usize>(), any::<usize>()).prop_map(|(from, to)| QueueCommand::Move { from, to }),
+			Just(QueueCommand::Clear),
+		]
+	}
+
+	fn command() -> impl Strategy<Value = Command> {
+		prop_oneof![
+			album_command().prop_map(Command::Album),
+			player_command().prop_map(Command::Player),
+			queue_command().prop_map(Command::Queue),
+		]
+	}
+
+	fn positional_command() -> impl Strategy<Value = PositionalCommand> {
+		(any::<usize>(), any::<Vec<String>>(), any::<bool>())
+			.prop_map(|(id, paths, force)| PositionalCommand::Add { id, paths, force })
+	}
+
+	fn list_command() -> impl Strategy<Value = ListCommand> {
+		(any::<usize>(), any::<Vec<String>>(), any::<Vec<usize>>())
+			.prop_map(|(id, genre, ids)| ListCommand::Set { id, genre, ids })
+	}
+
+	proptest! {
+		#[test]
+		fn display_round_trips(command in command()) {
+			prop_assert_eq!(command.to_string().parse::<Command>(), Ok(command));
+		}
+
+		#[test]
+		fn display_round_trips_positional(command in positional_command()) {
+			prop_assert_eq!(command.to_string().parse::<PositionalCommand>(), Ok(command));
+		}
+
+		#[test]
+		fn display_round_trips_list(command in list_command()) {
+			prop_assert_eq!(command.to_string().parse::<ListCommand>(), Ok(command));
+		}
 	}
 }
@@ -0,0 +1,145 @@
+use crate::player::RepeatMode;
+use serde::{Deserialize, Serialize};
+use sonas_macros::CommandValue;
+use std::fmt;
+
+/// Number of events buffered per subscriber before it is marked as lagged
+pub const EVENT_BUFFER: usize = 64;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Serialize, Deserialize, CommandValue)]
+#[serde(rename_all = "kebab-case")]
+pub enum Topic {
+	PlayerState,
+	CurrentTrack,
+	Queue,
+	Library,
+	Volume,
+	Options,
+}
+
+impl Topic {
+	pub const ALL: [Self; 6] = [
+		Self::PlayerState,
+		Self::CurrentTrack,
+		Self::Queue,
+		Self::Library,
+		Self::Volume,
+		Self::Options,
+	];
+}
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize, CommandValue)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlaybackState {
+	Playing,
+	Paused,
+	#[default]
+	Stopped,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "topic", rename_all = "kebab-case")]
+pub enum Event {
+	PlayerState {
+		state: PlaybackState,
+	},
+	CurrentTrack {
+		album: Option<usize>,
+		track: Option<u32>,
+	},
+	Queue {
+		length: usize,
+	},
+	Library {
+		albums: usize,
+	},
+	Volume {
+		level: u8,
+	},
+	Options {
+		shuffle: bool,
+		repeat: RepeatMode,
+	},
+}
+
+impl Event {
+	pub fn topic(&self) -> Topic {
+		match self {
+			Self::PlayerState { .. } => Topic::PlayerState,
+			Self::CurrentTrack { .. } => Topic::CurrentTrack,
+			Self::Queue { .. } => Topic::Queue,
+			Self::Library { .. } => Topic::Library,
+			Self::Volume { .. } => Topic::Volume,
+			Self::Options { .. } => Topic::Options,
+		}
+	}
+}
+
+impl fmt::Display for Event {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.topic())?;
+		match self {
+			Self::PlayerState { state } => write!(f, "\t{state}"),
+			Self::CurrentTrack { album, track } => {
+				let show = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+				write!(
+					f,
+					"\t{}\t{}",
+					show(album.map(|album| album.to_string())),
+					show(track.map(|track| track.to_string()))
+				)
+			}
+			Self::Queue { length } => write!(f, "\t{length}"),
+			Self::Library { albums } => write!(f, "\t{albums}"),
+			Self::Volume { level } => write!(f, "\t{level}%"),
+			Self::Options { shuffle, repeat } => write!(f, "\t{shuffle}\t{repeat}"),
+		}
+	}
+}
+
+/// Messages sent to subscribed clients without a matching request
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Push {
+	Event(Event),
+	Lagged { missed: u64 },
+}
+
+impl fmt::Display for Push {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Event(event) => writeln!(f, "event\t{event}"),
+			Self::Lagged { missed } => writeln!(f, "lagged\t{missed}"),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn pushes_serialise() {
+		let push = Push::Event(Event::Volume { level: 40 });
+		let json = serde_json::to_string(&push).unwrap();
+
+		assert_eq!(json, r#"{"event":{"topic":"volume","level":40}}"#);
+		assert_eq!(serde_json::from_str::<Push>(&json).unwrap(), push);
+		assert_eq!(
+			serde_json::to_string(&Push::Lagged { missed: 3 }).unwrap(),
+			r#"{"lagged":{"missed":3}}"#
+		);
+	}
+
+	#[test]
+	fn pushes_render_as_text() {
+		let push = Push::Event(Event::CurrentTrack {
+			album: Some(2),
+			track: None,
+		});
+
+		assert_eq!(push.to_string(), "event\tcurrent-track\t2\t-\n");
+		assert_eq!(Push::Lagged { missed: 3 }.to_string(), "lagged\t3\n");
+		assert_eq!("player-state".parse(), Ok(Topic::PlayerState));
+	}
+}
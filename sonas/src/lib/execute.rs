@@ -0,0 +1,686 @@
+use crate::{
+	AlbumCommand, Command, PlayerCommand, QueueCommand, SortDirection,
+	event::{EVENT_BUFFER, Event, PlaybackState},
+	library::{Album, Library, Track},
+	player::{Player, RepeatMode},
+};
+use serde::{Deserialize, Serialize};
+use serde_with::{DurationSecondsWithFrac, serde_as};
+use sonas_parser::{Percentage, Timestamp};
+use std::{
+	fmt,
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+use thiserror::Error;
+use tokio::sync::broadcast;
+
+/// Only ever locked for in-memory work, so async tasks may take the lock
+/// directly
+pub type SharedState = Arc<Mutex<State>>;
+
+#[derive(Debug, Clone)]
+pub struct State {
+	pub library: Library,
+	pub player: Player,
+	pub events: broadcast::Sender<Event>,
+}
+
+impl Default for State {
+	fn default() -> Self {
+		Self {
+			library: Library::default(),
+			player: Player::default(),
+			events: broadcast::channel(EVENT_BUFFER).0,
+		}
+	}
+}
+
+impl State {
+	pub fn emit(&self, event: Event) {
+		// sending only fails when nobody is subscribed
+		let _ = self.events.send(event);
+	}
+
+	/// Replaces the library and tells subscribers how many albums it has
+	pub fn set_library(&mut self, library: Library) {
+		let albums = library.albums.len();
+		self.library = library;
+		self.emit(Event::Library { albums });
+	}
+
+	/// Emits an event for everything about the player that differs from
+	/// `before`
+	fn emit_changes(&self, before: &Player) {
+		let player = &self.player;
+		if player.state != before.state {
+			self.emit(Event::PlayerState {
+				state: player.state,
+			});
+		}
+		if player.current_entry() != before.current_entry() {
+			let entry = player.current_entry();
+			self.emit(Event::CurrentTrack {
+				album: entry.map(|entry| entry.album),
+				track: entry.map(|entry| entry.track),
+			});
+		}
+		if player.queue != before.queue {
+			self.emit(Event::Queue {
+				length: player.queue.len(),
+			});
+		}
+		if player.volume != before.volume {
+			self.emit(Event::Volume {
+				level: player.volume,
+			});
+		}
+		if (player.shuffle, player.repeat) != (before.shuffle, before.repeat) {
+			self.emit(Event::Options {
+				shuffle: player.shuffle,
+				repeat: player.repeat,
+			});
+		}
+	}
+
+	fn song(&self, id: usize, number: u32) -> Song {
+		let album = self.library.album(id);
+		let track =
+			album.and_then(|album| album.tracks.iter().find(|track| track.number == number));
+
+		Song {
+			position: None,
+			id: None,
+			album: id,
+			album_title: album.map(|album| album.title.clone()).unwrap_or_default(),
+			artist: album.map(|album| album.artist.clone()).unwrap_or_default(),
+			year: album.and_then(|album| album.year),
+			track: track.cloned().unwrap_or_else(|| Track {
+				number,
+				title: String::new(),
+				duration: Duration::ZERO,
+			}),
+		}
+	}
+
+	fn queued_song(&self, position: usize) -> Option<Song> {
+		let entry = self.player.queue.get(position)?;
+		Some(Song {
+			position: Some(position),
+			id: Some(entry.id),
+			..self.song(entry.album, entry.track)
+		})
+	}
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Error)]
+pub enum ExecuteError {
+	#[error("no album with id {0}")]
+	UnknownAlbum(usize),
+	#[error("album {album} has no track {track}")]
+	UnknownTrack { album: usize, track: u32 },
+	#[error("no track at queue position {0}")]
+	NoSuchPosition(usize),
+	#[error("the queue is empty")]
+	EmptyQueue,
+	#[error("no track is playing")]
+	NothingPlaying,
+}
+
+pub trait Execute {
+	fn execute(self, state: &mut State) -> Result<Reply, ExecuteError>;
+}
+
+impl Execute for Command {
+	fn execute(self, state: &mut State) -> Result<Reply, ExecuteError> {
+		match self {
+			Self::Album(command) => command.execute(state),
+			Self::Player(command) => command.execute(state),
+			Self::Queue(command) => command.execute(state),
+		}
+	}
+}
+
+impl Execute for AlbumCommand {
+	fn execute(self, state: &mut State) -> Result<Reply, ExecuteError> {
+		match self {
+			Self::List { sort } => {
+				let mut albums = state
+					.library
+					.albums
+					.iter()
+					.map(AlbumSummary::from)
+					.collect::<Vec<_>>();
+				albums.sort_by_key(|album| album.id);
+				if sort == SortDirection::Descending {
+					albums.reverse();
+				}
+				Ok(Reply::Albums(albums))
+			}
+			Self::ListTracks { id } => state
+				.library
+				.album(id)
+				.map(|album| Reply::Tracks(album.tracks.clone()))
+				.ok_or(ExecuteError::UnknownAlbum(id)),
+			Self::Search {
+				any,
+				artist,
+				album,
+				title,
+				year,
+				exact,
+			} => {
+				let matches = |value: &str, filter: &Option<String>| match filter {
+					None => true,
+					Some(filter) if exact => value == filter,
+					Some(filter) => value.to_lowercase().contains(&filter.to_lowercase()),
+				};
+
+				let mut albums = state.library.albums.iter().collect::<Vec<_>>();
+				albums.sort_by_key(|album| album.id);
+				let songs = albums
+					.into_iter()
+					.filter(|found| {
+						matches(&found.artist, &artist) && matches(&found.title, &album)
+					})
+					.filter(|found| year.is_none_or(|year| found.year == Some(year)))
+					.flat_map(|found| {
+						found.tracks.iter().filter_map(|track| {
+							let any_matches = [&found.artist, &found.title, &track.title]
+								.into_iter()
+								.any(|value| matches(value, &any));
+							(any_matches && matches(&track.title, &title))
+								.then(|| state.song(found.id, track.number))
+						})
+					})
+					.collect();
+				Ok(Reply::Songs(songs))
+			}
+		}
+	}
+}
+
+impl Execute for PlayerCommand {
+	fn execute(self, state: &mut State) -> Result<Reply, ExecuteError> {
+		let before = state.player.clone();
+		let player = &mut state.player;
+
+		match self {
+			Self::Status => {
+				let current = (player.current).and_then(|position| state.queued_song(position));
+				let player = &state.player;
+				return Ok(Reply::Status(PlayerStatus {
+					state: player.state,
+					current,
+					elapsed: player.position,
+					volume: player.volume,
+					shuffle: player.shuffle,
+					repeat: player.repeat,
+					queue_length: player.queue.len(),
+				}));
+			}
+			Self::Play {
+				position: Some(position),
+			} => {
+				if position >= player.queue.len() {
+					return Err(ExecuteError::NoSuchPosition(position));
+				}
+				player.go_to(Some(position));
+				player.state = PlaybackState::Playing;
+			}
+			Self::Play { position: None } => {
+				if player.queue.is_empty() {
+					return Err(ExecuteError::EmptyQueue);
+				}
+				if player.current.is_none() {
+					player.go_to(Some(0));
+				}
+				player.state = PlaybackState::Playing;
+			}
+			Self::Pause => {
+				if player.state == PlaybackState::Playing {
+					player.state = PlaybackState::Paused;
+				}
+			}
+			Self::Toggle => {
+				let command = match player.state {
+					PlaybackState::Playing => Self::Pause,
+					_ => Self::Play { position: None },
+				};
+				return command.execute(state);
+			}
+			Self::Stop => {
+				player.state = PlaybackState::Stopped;
+				player.position = Duration::ZERO;
+			}
+			Self::Next => player.go_to(player.next_index()),
+			Self::Previous => player.go_to(player.previous_index()),
+			Self::Seek { time } => {
+				let entry = player.current_entry().ok_or(ExecuteError::NothingPlaying)?;
+				let duration = state
+					.library
+					.album(entry.album)
+					.and_then(|album| {
+						album
+							.tracks
+							.iter()
+							.find(|track| track.number == entry.track)
+					})
+					.map_or(Duration::MAX, |track| track.duration);
+				let player = &mut state.player;
+				player.position = time.apply(Timestamp(player.position)).0.min(duration);
+			}
+			Self::Volume { level } => {
+				let current = Percentage::new(player.volume).unwrap_or(Percentage::MAX);
+				player.volume = level.apply(current).get();
+			}
+			Self::Shuffle { enabled } => player.shuffle = enabled,
+			Self::Repeat { mode } => player.repeat = mode,
+		}
+
+		state.emit_changes(&before);
+		Ok(Reply::Ack)
+	}
+}
+
+impl Execute for QueueCommand {
+	fn execute(self, state: &mut State) -> Result<Reply, ExecuteError> {
+		let before = state.player.clone();
+		let len = state.player.queue.len();
+
+		match self {
+			Self::List => {
+				let songs = (0..len)
+					.filter_map(|position| state.queued_song(position))
+					.collect();
+				return Ok(Reply::Songs(songs));
+			}
+			Self::Add { album: id, track } => {
+				let album = state
+					.library
+					.album(id)
+					.ok_or(ExecuteError::UnknownAlbum(id))?;
+				let tracks = album
+					.tracks
+					.iter()
+					.map(|track| track.number)
+					.filter(|&number| track.is_none_or(|track| track == number))
+					.collect::<Vec<_>>();
+				if let (Some(track), []) = (track, tracks.as_slice()) {
+					return Err(ExecuteError::UnknownTrack { album: id, track });
+				}
+				for track in tracks {
+					state.player.push(id, track);
+				}
+			}
+			Self::Delete { position, count } => {
+				if position >= len {
+					return Err(ExecuteError::NoSuchPosition(position));
+				}
+				state
+					.player
+					.remove(position, position.saturating_add(count).min(len));
+			}
+			Self::Move { from, to } => {
+				if let Some(position) = [from, to].into_iter().find(|&position| position >= len) {
+					return Err(ExecuteError::NoSuchPosition(position));
+				}
+				state.player.move_entry(from, to);
+			}
+			Self::Clear => state.player.remove(0, len),
+		}
+
+		state.emit_changes(&before);
+		Ok(Reply::Ack)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "kebab-case")]
+pub enum Reply {
+	Ack,
+	Help(String),
+	Albums(Vec<AlbumSummary>),
+	Tracks(Vec<Track>),
+	Songs(Vec<Song>),
+	Status(PlayerStatus),
+}
+
+/// A track together with its album, and its queue position and entry id when
+/// it was listed from the queue
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Song {
+	pub position: Option<usize>,
+	pub id: Option<u64>,
+	pub album: usize,
+	pub album_title: String,
+	pub artist: String,
+	pub year: Option<u16>,
+	#[serde(flatten)]
+	pub track: Track,
+}
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlayerStatus {
+	pub state: PlaybackState,
+	pub current: Option<Song>,
+	#[serde_as(as = "DurationSecondsWithFrac<f64>")]
+	pub elapsed: Duration,
+	pub volume: u8,
+	pub shuffle: bool,
+	pub repeat: RepeatMode,
+	pub queue_length: usize,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AlbumSummary {
+	pub id: usize,
+	pub title: String,
+	pub artist: String,
+	pub year: Option<u16>,
+	pub track_count: usize,
+}
+
+impl From<&Album> for AlbumSummary {
+	fn from(album: &Album) -> Self {
+		Self {
+			id: album.id,
+			title: album.title.clone(),
+			artist: album.artist.clone(),
+			year: album.year,
+			track_count: album.tracks.len(),
+		}
+	}
+}
+
+/// One record per line with tab separated fields
+impl fmt::Display for Reply {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Ack => writeln!(f, "ok"),
+			Self::Help(text) if text.ends_with('\n') => f.write_str(text),
+			Self::Help(text) => writeln!(f, "{text}"),
+			Self::Albums(albums) => albums.iter().try_for_each(|album| {
+				let year = album.year.map_or("-".to_string(), |year| year.to_string());
+				writeln!(
+					f,
+					"{}\t{}\t{}\t{year}\t{}",
+					album.id, album.artist, album.title, album.track_count
+				)
+			}),
+			Self::Tracks(tracks) => tracks.iter().try_for_each(|track| {
+				writeln!(
+					f,
+					"{}\t{}\t{}",
+					track.number,
+					track.title,
+					Timestamp(track.duration)
+				)
+			}),
+			Self::Songs(songs) => songs.iter().try_for_each(|song| {
+				let position = song
+					.position
+					.map_or("-".to_string(), |position| position.to_string());
+				let id = song.id.map_or("-".to_string(), |id| id.to_string());
+				writeln!(
+					f,
+					"{position}\t{id}\t{}\t{}\t{}\t{}\t{}\t{}",
+					song.album,
+					song.track.number,
+					song.artist,
+					song.album_title,
+					song.track.title,
+					Timestamp(song.track.duration)
+				)
+			}),
+			Self::Status(status) => {
+				writeln!(f, "state\t{}", status.state)?;
+				if let Some(song) = &status.current {
+					let position = song.position.unwrap_or_default();
+					writeln!(f, "position\t{position}")?;
+					writeln!(f, "album\t{}", song.album)?;
+					writeln!(f, "track\t{}", song.track.number)?;
+					writeln!(f, "elapsed\t{}", Timestamp(status.elapsed))?;
+					writeln!(f, "duration\t{}", Timestamp(song.track.duration))?;
+				}
+				writeln!(f, "volume\t{}%", status.volume)?;
+				writeln!(f, "shuffle\t{}", status.shuffle)?;
+				writeln!(f, "repeat\t{}", status.repeat)?;
+				writeln!(f, "queue\t{}", status.queue_length)
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::time::Duration;
+
+	fn state() -> State {
+		let album = |id, title: &str, tracks: &[(&str, u64)]| Album {
+			id,
+			title: title.to_string(),
+			artist: "Artist".to_string(),
+			year: (id == 1).then_some(2001),
+			tracks: tracks
+				.iter()
+				.zip(1..)
+				.map(|(&(title, seconds), number)| Track {
+					number,
+					title: title.to_string(),
+					duration: Duration::from_secs(seconds),
+				})
+				.collect(),
+		};
+
+		State {
+			library: Library {
+				albums: vec![
+					album(2, "Second", &[("Intro", 62)]),
+					album(1, "First", &[("One", 201), ("Two", 3725)]),
+				],
+			},
+			..State::default()
+		}
+	}
+
+	#[test]
+	fn lists_albums() {
+		let mut state = state();
+		let reply = "album list sort=asc"
+			.parse::<Command>()
+			.unwrap()
+			.execute(&mut state);
+
+		assert_eq!(
+			reply.map(|reply| reply.to_string()),
+			Ok("1\tArtist\tFirst\t2001\t2\n2\tArtist\tSecond\t-\t1\n".to_string())
+		);
+
+		let Ok(Reply::Albums(albums)) =
+			"album list".parse::<Command>().unwrap().execute(&mut state)
+		else {
+			panic!("expected an album list");
+		};
+		assert_eq!(
+			albums.iter().map(|album| album.id).collect::<Vec<_>>(),
+			vec![2, 1]
+		);
+	}
+
+	#[test]
+	fn lists_tracks() {
+		let mut state = state();
+		let reply = "album list-tracks 1"
+			.parse::<Command>()
+			.unwrap()
+			.execute(&mut state);
+
+		assert_eq!(
+			reply.map(|reply| reply.to_string()),
+			Ok("1\tOne\t3:21\n2\tTwo\t1:02:05\n".to_string())
+		);
+
+		let reply = "album list-tracks 3"
+			.parse::<Command>()
+			.unwrap()
+			.execute(&mut state);
+		assert_eq!(reply, Err(ExecuteError::UnknownAlbum(3)));
+	}
+
+	fn run(state: &mut State, command: &str) -> Result<Reply, ExecuteError> {
+		command.parse::<Command>().unwrap().execute(state)
+	}
+
+	#[test]
+	fn plays_the_queue() {
+		let mut state = state();
+		let mut events = state.events.subscribe();
+
+		assert_eq!(
+			run(&mut state, "player play"),
+			Err(ExecuteError::EmptyQueue)
+		);
+		run(&mut state, "queue add 1").unwrap();
+		run(&mut state, "queue add 2 1").unwrap();
+		assert_eq!(
+			run(&mut state, "queue add 2 5"),
+			Err(ExecuteError::UnknownTrack { album: 2, track: 5 })
+		);
+		run(&mut state, "player play").unwrap();
+		run(&mut state, "player seek +1:00").unwrap();
+		run(&mut state, "player volume -30%").unwrap();
+
+		assert_eq!(events.try_recv(), Ok(Event::Queue { length: 2 }));
+		assert_eq!(events.try_recv(), Ok(Event::Queue { length: 3 }));
+		assert_eq!(
+			events.try_recv(),
+			Ok(Event::PlayerState {
+				state: PlaybackState::Playing
+			})
+		);
+		assert_eq!(
+			events.try_recv(),
+			Ok(Event::CurrentTrack {
+				album: Some(1),
+				track: Some(1)
+			})
+		);
+		assert_eq!(events.try_recv(), Ok(Event::Volume { level: 70 }));
+
+		let Ok(Reply::Status(status)) = run(&mut state, "player status") else {
+			panic!("expected the player status");
+		};
+		assert_eq!(status.elapsed, Duration::from_secs(60));
+		assert_eq!(
+			status.current.map(|song| song.track.title),
+			Some("One".into())
+		);
+
+		run(&mut state, "player next").unwrap();
+		run(&mut state, "player next").unwrap();
+		assert_eq!(state.player.current, Some(2));
+		run(&mut state, "player next").unwrap();
+		assert_eq!(state.player.current, None);
+		assert_eq!(state.player.state, PlaybackState::Stopped);
+	}
+
+	#[test]
+	fn edits_the_queue() {
+		let mut state = state();
+		run(&mut state, "queue add 1").unwrap();
+		run(&mut state, "queue add 2").unwrap();
+		run(&mut state, "player play 1").unwrap();
+
+		run(&mut state, "queue move 1 2").unwrap();
+		assert_eq!(state.player.current, Some(2));
+		run(&mut state, "queue delete 0").unwrap();
+		assert_eq!(state.player.current, Some(1));
+		assert_eq!(
+			run(&mut state, "queue move 0 2"),
+			Err(ExecuteError::NoSuchPosition(2))
+		);
+
+		assert_eq!(
+			run(&mut state, "queue list").map(|reply| reply.to_string()),
+			Ok(
+				"0\t2\t2\t1\tArtist\tSecond\tIntro\t1:02\n1\t1\t1\t2\tArtist\tFirst\tTwo\t1:02:05\n"
+					.to_string()
+			)
+		);
+
+		run(&mut state, "queue delete 1 count=5").unwrap();
+		assert_eq!(state.player.current, None);
+		assert_eq!(state.player.state, PlaybackState::Stopped);
+	}
+
+	#[test]
+	fn searches_tracks() {
+		let mut state = state();
+		let titles = |reply| match reply {
+			Ok(Reply::Songs(songs)) => songs
+				.into_iter()
+				.map(|song| song.track.title)
+				.collect::<Vec<_>>(),
+			other => panic!("expected songs, got {other:?}"),
+		};
+
+		assert_eq!(
+			titles(run(&mut state, "album search any=o")),
+			["One", "Two", "Intro"]
+		);
+		assert_eq!(
+			titles(run(&mut state, "album search album=first title=T")),
+			["Two"]
+		);
+		assert!(titles(run(&mut state, "album search album=first exact=true")).is_empty());
+		assert_eq!(titles(run(&mut state, "album search year=2001")).len(), 2);
+	}
+
+	#[test]
+	fn replies_serialise_to_json() {
+		let reply = Reply::Tracks(vec![Track {
+			number: 1,
+			title: "One".to_string(),
+			duration: Duration::from_millis(1500),
+		}]);
+
+		assert_eq!(
+			serde_json::to_string(&reply).unwrap(),
+			r#"{"type":"tracks","data":[{"number":1,"title":"One","duration":1.5}]}"#
+		);
+		assert_eq!(
+			serde_json::to_string(&Reply::Ack).unwrap(),
+			r#"{"type":"ack"}"#
+		);
+	}
+
+	#[test]
+	fn library_changes_are_emitted() {
+		let mut state = State::default();
+		let mut events = state.events.subscribe();
+		let library = self::state().library;
+		state.set_library(library.clone());
+
+		assert_eq!(state.library, library);
+		assert_eq!(events.try_recv(), Ok(Event::Library { albums: 2 }));
+	}
+
+	#[test]
+	fn slow_subscribers_lag() {
+		let state = State::default();
+		let mut events = state.events.subscribe();
+
+		for level in 0..EVENT_BUFFER as u8 + 2 {
+			state.emit(Event::Volume { level });
+		}
+
+		assert_eq!(
+			events.try_recv(),
+			Err(broadcast::error::TryRecvError::Lagged(2))
+		);
+		assert_eq!(events.try_recv(), Ok(Event::Volume { level: 2 }));
+	}
+}
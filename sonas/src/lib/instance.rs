@@ -0,0 +1,378 @@
+use crate::paths;
+use ::config::{Config, ConfigError, File as ConfigFile};
+use interprocess::local_socket::{
+	GenericFilePath, GenericNamespaced, Name, Stream, ToFsName, ToNsName, prelude::*,
+};
+use serde::Deserialize;
+use std::{
+	env,
+	fs::{self, File, TryLockError},
+	io::{self, Read, Seek, Write},
+	net::IpAddr,
+	path::{Path, PathBuf},
+	process::{Command, Stdio},
+};
+use thiserror::Error;
+
+pub const SOCKET_ENV: &str = "SONAS_SOCKET";
+pub const INSTANCE_ENV: &str = "SONAS_INSTANCE";
+const DEFAULT_SOCKET: &str = "sonasd.sock";
+
+/// The `[daemon]` section of the config file
+#[derive(Debug, Clone, Default, Eq, PartialEq, Deserialize)]
+pub struct DaemonConfig {
+	pub socket: Option<String>,
+	pub instance: Option<String>,
+	/// TCP address sonasd accepts MPD clients on
+	pub mpd: Option<String>,
+	pub http: Option<HttpConfig>,
+	/// File listing the albums sonasd serves
+	pub library: Option<PathBuf>,
+}
+
+/// The `[daemon.http]` section, the HTTP API is only served when it exists
+#[derive(Debug, Clone, Default, Eq, PartialEq, Deserialize)]
+pub struct HttpConfig {
+	pub address: Option<IpAddr>,
+	pub port: Option<u16>,
+	/// Bearer token every request has to carry
+	pub token: Option<String>,
+}
+
+impl DaemonConfig {
+	pub fn load(path: &Path) -> Result<Self, ConfigError> {
+		let path = path
+			.to_str()
+			.ok_or_else(|| ConfigError::Message("invalid config path".to_string()))?;
+		let config = Config::builder()
+			.add_source(ConfigFile::with_name(path).required(false))
+			.build()?;
+
+		match config.get::<Self>("daemon") {
+			Err(ConfigError::NotFound(_)) => Ok(Self::default()),
+			daemon => daemon,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Error)]
+#[error("invalid instance name '{0}', only letters, digits, '-' and '_' are allowed")]
+pub struct InvalidInstanceError(pub String);
+
+impl From<InvalidInstanceError> for io::Error {
+	fn from(error: InvalidInstanceError) -> Self {
+		io::Error::new(io::ErrorKind::InvalidInput, error)
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum ClaimError {
+	#[error("sonasd is already running on {socket}{}", pid.map(|pid| format!(" (pid {pid})")).unwrap_or_default())]
+	Running { socket: String, pid: Option<u32> },
+	#[error("failed to lock pid file {}: {source}", path.display())]
+	Lock { path: PathBuf, source: io::Error },
+	#[error("failed to remove stale socket {socket}: {source}")]
+	Stale { socket: String, source: io::Error },
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DaemonStatus {
+	Stopped,
+	Running { pid: Option<u32> },
+}
+
+/// The socket a sonasd instance listens on, either a namespaced name or a
+/// file path
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Instance {
+	pub socket: String,
+}
+
+impl Default for Instance {
+	fn default() -> Self {
+		Self {
+			socket: DEFAULT_SOCKET.to_string(),
+		}
+	}
+}
+
+impl Instance {
+	pub fn named(name: &str) -> Self {
+		Self {
+			socket: format!("sonasd-{name}.sock"),
+		}
+	}
+
+	/// Picks the socket given on the command line, then the one from the
+	/// environment and finally the one in the config file. An invalid
+	/// instance name is an error rather than a reason to fall back
+	pub fn resolve(
+		socket: Option<String>,
+		instance: Option<String>,
+	) -> Result<Self, InvalidInstanceError> {
+		let from = |socket: Option<String>, instance: Option<String>| match (socket, instance) {
+			(Some(socket), _) => Some(Ok(Self { socket })),
+			(None, Some(name)) => Some(parse_name(&name).map(|name| Self::named(&name))),
+			(None, None) => None,
+		};
+		let config = paths::config_path()
+			.and_then(|path| DaemonConfig::load(&path).ok())
+			.unwrap_or_default();
+
+		from(socket, instance)
+			.or_else(|| from(env::var(SOCKET_ENV).ok(), env::var(INSTANCE_ENV).ok()))
+			.or_else(|| from(config.socket, config.instance))
+			.unwrap_or_else(|| Ok(Self::default()))
+	}
+
+	pub fn is_path(&self) -> bool {
+		self.socket.contains(['/', '\\'])
+	}
+
+	pub fn name(&self) -> io::Result<Name<'_>> {
+		match self.is_path() {
+			true => self.socket.as_str().to_fs_name::<GenericFilePath>(),
+			false => self.socket.as_str().to_ns_name::<GenericNamespaced>(),
+		}
+	}
+
+	/// The pid file doubles as the single-instance lock
+	pub fn pid_path(&self) -> PathBuf {
+		match self.is_path() {
+			true => PathBuf::from(format!("{}.pid", self.socket)),
+			false => paths::runtime_dir().join(format!("{}.pid", self.socket)),
+		}
+	}
+
+	pub fn log_path(&self) -> PathBuf {
+		match self.is_path() {
+			true => PathBuf::from(format!("{}.log", self.socket)),
+			false => paths::state_dir().join(format!("{}.log", self.socket)),
+		}
+	}
+
+	/// Named after the whole socket, with path sockets made absolute and
+	/// escaped so every instance gets a state file of its own
+	pub fn state_path(&self) -> PathBuf {
+		let socket = match self.is_path() {
+			true => std::path::absolute(&self.socket).map_or(self.socket.clone(), |path| {
+				path.to_string_lossy().into_owned()
+			}),
+			false => self.socket.clone(),
+		};
+		let mut name = String::with_capacity(socket.len());
+		for c in socket.chars() {
+			match c {
+				'%' | '/' | '\\' | ':' => name.push_str(&format!("%{:02X}", c as u32)),
+				c => name.push(c),
+			}
+		}
+		paths::data_dir().join(format!("{name}.state.json"))
+	}
+
+	/// Checks whether a daemon holds the lock on the pid file
+	pub fn status(&self) -> io::Result<DaemonStatus> {
+		let mut file = match File::open(self.pid_path()) {
+			Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(DaemonStatus::Stopped),
+			file => file?,
+		};
+		match file.try_lock_shared() {
+			Ok(()) => Ok(DaemonStatus::Stopped),
+			Err(TryLockError::WouldBlock) => Ok(DaemonStatus::Running {
+				pid: read_pid(&mut file),
+			}),
+			Err(TryLockError::Error(e)) => Err(e),
+		}
+	}
+
+	/// Starts a detached sonasd for this instance and waits until it accepts
+	/// connections
+	pub fn spawn(&self) -> io::Result<()> {
+		let output = Command::new(sonasd_path())
+			.args(["--daemonize", "--socket", &self.socket])
+			.stdin(Stdio::null())
+			.output()?;
+		match output.status.success() {
+			true => Ok(()),
+			false => Err(io::Error::other(format!(
+				"failed to start sonasd: {}",
+				String::from_utf8_lossy(&output.stderr).trim()
+			))),
+		}
+	}
+
+	/// Takes the single-instance lock and removes a socket left behind by a
+	/// daemon that did not shut down cleanly. The lock is held until the
+	/// returned file is dropped
+	pub fn claim(&self) -> Result<File, ClaimError> {
+		let path = self.pid_path();
+		let lock_error = |source| ClaimError::Lock {
+			path: path.clone(),
+			source,
+		};
+
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent).map_err(lock_error)?;
+		}
+		let mut file = File::options()
+			.read(true)
+			.write(true)
+			.create(true)
+			.truncate(false)
+			.open(&path)
+			.map_err(lock_error)?;
+
+		match file.try_lock() {
+			Ok(()) => (),
+			Err(TryLockError::WouldBlock) => {
+				return Err(ClaimError::Running {
+					socket: self.socket.clone(),
+					pid: read_pid(&mut file),
+				});
+			}
+			Err(TryLockError::Error(e)) => return Err(lock_error(e)),
+		}
+
+		// a daemon that predates the lock file would still answer
+		if Stream::connect(self.name().map_err(lock_error)?).is_ok() {
+			return Err(ClaimError::Running {
+				socket: self.socket.clone(),
+				pid: None,
+			});
+		}
+		if self.is_path() {
+			match fs::remove_file(&self.socket) {
+				Err(e) if e.kind() != io::ErrorKind::NotFound => {
+					return Err(ClaimError::Stale {
+						socket: self.socket.clone(),
+						source: e,
+					});
+				}
+				_ => (),
+			}
+		}
+
+		file.set_len(0)
+			.and_then(|()| file.rewind())
+			.and_then(|()| writeln!(file, "{}", std::process::id()))
+			.map_err(lock_error)?;
+		Ok(file)
+	}
+}
+
+/// Whether a failed connection means that no daemon is listening
+pub fn is_not_running(error: &io::Error) -> bool {
+	matches!(
+		error.kind(),
+		io::ErrorKind::NotFound | io::ErrorKind::ConnectionRefused
+	)
+}
+
+fn read_pid(file: &mut File) -> Option<u32> {
+	let mut pid = String::new();
+	file.read_to_string(&mut pid).ok()?;
+	pid.trim().parse().ok()
+}
+
+/// Prefers the sonasd installed next to the running binary over the one in
+/// `PATH`
+fn sonasd_path() -> PathBuf {
+	let name = format!("sonasd{}", env::consts::EXE_SUFFIX);
+	env::current_exe()
+		.ok()
+		.and_then(|exe| Some(exe.parent()?.join(&name)))
+		.filter(|path| path.exists())
+		.unwrap_or_else(|| PathBuf::from(name))
+}
+
+pub fn parse_name(name: &str) -> Result<String, InvalidInstanceError> {
+	match !name.is_empty()
+		&& name
+			.chars()
+			.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+	{
+		true => Ok(name.to_string()),
+		false => Err(InvalidInstanceError(name.to_string())),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn instances_have_their_own_socket() {
+		assert_eq!(Instance::default().socket, "sonasd.sock");
+		assert_eq!(Instance::named("work").socket, "sonasd-work.sock");
+		assert_eq!(
+			Instance::resolve(Some("/tmp/s.sock".to_string()), Some("work".to_string())),
+			Ok(Instance {
+				socket: "/tmp/s.sock".to_string()
+			})
+		);
+		assert_eq!(
+			Instance::resolve(None, Some("work".to_string())),
+			Ok(Instance::named("work"))
+		);
+		assert_eq!(
+			Instance::resolve(None, Some("../x".to_string())),
+			Err(InvalidInstanceError("../x".to_string()))
+		);
+		assert!(parse_name("work-2").is_ok());
+		assert!(parse_name("../x").is_err());
+	}
+
+	#[test]
+	fn instances_have_their_own_state_file() {
+		let state_path = |socket: &str| {
+			Instance {
+				socket: socket.to_string(),
+			}
+			.state_path()
+		};
+		let paths = [
+			state_path("/a/s.sock"),
+			state_path("/b/s.sock"),
+			state_path("s.sock"),
+			state_path("%2Fa%2Fs.sock"),
+		];
+		for (i, path) in paths.iter().enumerate() {
+			assert_eq!(path.parent(), Some(paths::data_dir().as_path()));
+			assert!(!paths[..i].contains(path), "{} is shared", path.display());
+		}
+		assert_eq!(
+			state_path("s.sock"),
+			paths::data_dir().join("s.sock.state.json")
+		);
+	}
+
+	#[test]
+	fn only_one_daemon_claims_an_instance() {
+		let dir = env::temp_dir().join(format!("sonas-claim-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let socket = dir.join("test.sock");
+		fs::write(&socket, "").unwrap();
+		let instance = Instance {
+			socket: socket.to_str().unwrap().to_string(),
+		};
+
+		let lock = instance.claim().unwrap();
+		assert!(!socket.exists(), "the stale socket is removed");
+		match instance.claim() {
+			Err(ClaimError::Running { pid, .. }) => assert_eq!(pid, Some(std::process::id())),
+			other => panic!("expected the instance to be taken, got {other:?}"),
+		}
+
+		assert_eq!(
+			instance.status().unwrap(),
+			DaemonStatus::Running {
+				pid: Some(std::process::id())
+			}
+		);
+
+		drop(lock);
+		assert_eq!(instance.status().unwrap(), DaemonStatus::Stopped);
+		assert!(instance.claim().is_ok());
+		fs::remove_dir_all(dir).unwrap();
+	}
+}
@@ -0,0 +1,77 @@
+use ::config::{Config, ConfigError, File as ConfigFile};
+use serde::{Deserialize, Serialize};
+use serde_with::{DurationSecondsWithFrac, serde_as};
+use std::{path::Path, time::Duration};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Library {
+	pub albums: Vec<Album>,
+}
+
+impl Library {
+	/// Reads the albums from a file in any format the config file may use,
+	/// a missing file is an empty library
+	pub fn load(path: &Path) -> Result<Self, ConfigError> {
+		Config::builder()
+			.add_source(ConfigFile::from(path).required(false))
+			.build()?
+			.try_deserialize()
+	}
+
+	pub fn album(&self, id: usize) -> Option<&Album> {
+		self.albums.iter().find(|album| album.id == id)
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Album {
+	pub id: usize,
+	pub title: String,
+	pub artist: String,
+	pub year: Option<u16>,
+	pub tracks: Vec<Track>,
+}
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Track {
+	pub number: u32,
+	pub title: String,
+	#[serde_as(as = "DurationSecondsWithFrac<f64>")]
+	pub duration: Duration,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::{env, fs};
+
+	#[test]
+	fn loads_albums_from_a_file() {
+		let path = env::temp_dir().join(format!("sonas-library-{}.toml", std::process::id()));
+		assert_eq!(Library::load(&path).unwrap(), Library::default());
+
+		fs::write(
+			&path,
+			r#"
+			[[albums]]
+			id = 1
+			title = "Blue"
+			artist = "Joni Mitchell"
+			year = 1971
+			tracks = [
+				{ number = 1, title = "All I Want", duration = 214 },
+				{ number = 2, title = "My Old Man", duration = 213.5 },
+			]
+			"#,
+		)
+		.unwrap();
+		let library = Library::load(&path).unwrap();
+		fs::remove_file(&path).unwrap();
+		let album = library.album(1).unwrap();
+		assert_eq!(album.year, Some(1971));
+		assert_eq!(album.tracks[0].duration, Duration::from_secs(214));
+		assert_eq!(album.tracks[1].duration, Duration::from_millis(213_500));
+	}
+}
@@ -1,4 +1,14 @@
+pub mod alias;
 mod command;
+pub mod event;
+pub mod execute;
+pub mod instance;
+pub mod library;
+pub mod paths;
+pub mod persist;
+pub mod player;
+pub mod protocol;
+pub mod script;
 pub mod server;
 
 pub use command::*;
@@ -0,0 +1,49 @@
+use directories::ProjectDirs;
+use std::{env, path::PathBuf};
+
+pub const CONFIG_FILE: &str = "config";
+pub const RC_FILE: &str = "sonasrc";
+pub const LIBRARY_FILE: &str = "library.toml";
+
+pub fn project_dirs() -> Option<ProjectDirs> {
+	ProjectDirs::from("net", "LunaPresent", "sonas")
+}
+
+pub fn config_path() -> Option<PathBuf> {
+	Some(project_dirs()?.config_dir().join(CONFIG_FILE))
+}
+
+pub fn rc_path() -> Option<PathBuf> {
+	Some(project_dirs()?.config_dir().join(RC_FILE))
+}
+
+/// Directory for lock files, falling back to the temporary directory on
+/// platforms without a runtime directory
+pub fn runtime_dir() -> PathBuf {
+	project_dirs()
+		.and_then(|dirs| Some(dirs.runtime_dir()?.to_path_buf()))
+		.unwrap_or_else(|| env::temp_dir().join("sonas"))
+}
+
+/// Directory for log files
+pub fn state_dir() -> PathBuf {
+	project_dirs()
+		.map(|dirs| {
+			dirs.state_dir()
+				.unwrap_or_else(|| dirs.data_local_dir())
+				.to_path_buf()
+		})
+		.unwrap_or_else(|| env::temp_dir().join("sonas"))
+}
+
+/// Directory for state that is kept across restarts
+pub fn data_dir() -> PathBuf {
+	project_dirs()
+		.map(|dirs| dirs.data_dir().to_path_buf())
+		.unwrap_or_else(|| env::temp_dir().join("sonas"))
+}
+
+/// File the albums are read from unless the config names another one
+pub fn library_path() -> PathBuf {
+	data_dir().join(LIBRARY_FILE)
+}
@@ -0,0 +1,204 @@
+use crate::player::Player;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+	fs::{self, File},
+	io::{self, Write},
+	path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+pub const STATE_VERSION: u64 = 2;
+
+/// Upgrades a state file by one version, `MIGRATIONS[n]` turns version `n + 1`
+/// into version `n + 2`
+type Migration = fn(Value) -> Result<Value, PersistError>;
+const MIGRATIONS: &[Migration] = &[number_queue_entries];
+
+#[derive(Debug, Error)]
+pub enum PersistError {
+	#[error(transparent)]
+	Io(#[from] io::Error),
+	#[error("invalid state file: {0}")]
+	Invalid(#[from] serde_json::Error),
+	#[error("state file has no version")]
+	MissingVersion,
+	#[error("state file version {0} is newer than the supported version {STATE_VERSION}")]
+	Newer(u64),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StateFile {
+	version: u64,
+	player: Player,
+}
+
+/// Reads the state saved by an earlier daemon, migrating older versions.
+/// A missing file is not an error
+pub fn load(path: &Path) -> Result<Option<Player>, PersistError> {
+	let text = match fs::read_to_string(path) {
+		Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+		text => text?,
+	};
+	let mut value = serde_json::from_str::<Value>(&text)?;
+
+	let version = value
+		.get("version")
+		.and_then(Value::as_u64)
+		.filter(|&version| version > 0)
+		.ok_or(PersistError::MissingVersion)?;
+	if version > STATE_VERSION {
+		return Err(PersistError::Newer(version));
+	}
+	for migrate in &MIGRATIONS[version as usize - 1..] {
+		value = migrate(value)?;
+	}
+	value["version"] = STATE_VERSION.into();
+
+	Ok(Some(serde_json::from_value::<StateFile>(value)?.player))
+}
+
+/// Writes to a temporary file next to `path` and renames it over the old
+/// state, so a crash never leaves a half written file behind
+pub fn save(path: &Path, player: &Player) -> Result<(), PersistError> {
+	let state = StateFile {
+		version: STATE_VERSION,
+		player: player.clone(),
+	};
+	let text = serde_json::to_string_pretty(&state)?;
+
+	if let Some(parent) = path.parent() {
+		fs::create_dir_all(parent)?;
+	}
+	let temporary = temporary_path(path);
+	let mut file = File::create(&temporary)?;
+	file.write_all(text.as_bytes())?;
+	file.sync_all()?;
+	fs::rename(&temporary, path)?;
+	Ok(())
+}
+
+/// Moves a state file that could not be loaded out of the way, so saving
+/// does not overwrite what a newer daemon or a manual fix could recover
+pub fn set_aside(path: &Path) -> io::Result<PathBuf> {
+	let mut backup = path.as_os_str().to_owned();
+	backup.push(".bak");
+	fs::rename(path, &backup)?;
+	Ok(backup.into())
+}
+
+/// Version 2 gives every queue entry an id
+fn number_queue_entries(mut value: Value) -> Result<Value, PersistError> {
+	let Some(player) = value.get_mut("player").and_then(Value::as_object_mut) else {
+		return Ok(value);
+	};
+	let mut next_id = 0;
+	if let Some(queue) = player.get_mut("queue").and_then(Value::as_array_mut) {
+		for entry in queue.iter_mut().filter_map(Value::as_object_mut) {
+			entry.insert("id".to_string(), next_id.into());
+			next_id += 1;
+		}
+	}
+	player.insert("next_id".to_string(), next_id.into());
+	Ok(value)
+}
+
+fn temporary_path(path: &Path) -> PathBuf {
+	let mut temporary = path.as_os_str().to_owned();
+	temporary.push(".tmp");
+	temporary.into()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		event::PlaybackState,
+		player::{QueueEntry, RepeatMode},
+	};
+	use std::{env, time::Duration};
+
+	fn path(name: &str) -> PathBuf {
+		env::temp_dir().join(format!("sonas-{name}-{}.json", std::process::id()))
+	}
+
+	#[test]
+	fn state_round_trips() {
+		let path = path("state");
+		let player = Player {
+			state: PlaybackState::Stopped,
+			queue: vec![QueueEntry {
+				id: 3,
+				album: 1,
+				track: 2,
+			}],
+			current: Some(0),
+			position: Duration::from_millis(61500),
+			volume: 40,
+			shuffle: true,
+			repeat: RepeatMode::All,
+			next_id: 4,
+		};
+
+		assert!(load(&path).unwrap().is_none());
+		save(&path, &player).unwrap();
+		let loaded = load(&path).unwrap();
+		fs::remove_file(&path).unwrap();
+
+		assert_eq!(loaded, Some(player));
+		assert!(!temporary_path(&path).exists());
+	}
+
+	#[test]
+	fn unknown_versions_are_rejected() {
+		let path = path("newer");
+		fs::write(&path, r#"{"version":99,"player":{}}"#).unwrap();
+		let newer = load(&path);
+		fs::write(&path, r#"{"player":{}}"#).unwrap();
+		let missing = load(&path);
+		fs::remove_file(&path).unwrap();
+
+		assert!(matches!(newer, Err(PersistError::Newer(99))));
+		assert!(matches!(missing, Err(PersistError::MissingVersion)));
+	}
+
+	#[test]
+	fn missing_fields_use_defaults() {
+		let path = path("partial");
+		fs::write(&path, r#"{"version":2,"player":{"volume":20}}"#).unwrap();
+		let loaded = load(&path);
+		fs::remove_file(&path).unwrap();
+
+		assert_eq!(
+			loaded.unwrap(),
+			Some(Player {
+				volume: 20,
+				..Player::default()
+			})
+		);
+	}
+
+	#[test]
+	fn queue_entries_get_ids() {
+		let path = path("version-1");
+		let queue = r#"[{"album":1,"track":2},{"album":1,"track":3}]"#;
+		fs::write(
+			&path,
+			format!(r#"{{"version":1,"player":{{"queue":{queue}}}}}"#),
+		)
+		.unwrap();
+		let loaded = load(&path);
+		fs::remove_file(&path).unwrap();
+
+		let player = loaded.unwrap().unwrap();
+		assert_eq!(
+			player
+				.queue
+				.iter()
+				.map(|entry| entry.id)
+				.collect::<Vec<_>>(),
+			[0, 1]
+		);
+		assert_eq!(player.next_id, 2);
+	}
+}
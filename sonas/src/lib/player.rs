@@ -0,0 +1,136 @@
+use crate::event::PlaybackState;
+use serde::{Deserialize, Serialize};
+use serde_with::{DurationSecondsWithFrac, serde_as};
+use sonas_macros::CommandValue;
+use std::{
+	hash::{BuildHasher, RandomState},
+	time::Duration,
+};
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Serialize, Deserialize, CommandValue)]
+#[serde(rename_all = "kebab-case")]
+pub enum RepeatMode {
+	#[default]
+	Off,
+	One,
+	All,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+pub struct QueueEntry {
+	/// Stays the same while the entry moves around the queue
+	pub id: u64,
+	pub album: usize,
+	pub track: u32,
+}
+
+/// Everything about playback that survives a restart of the daemon
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Player {
+	/// A restarted daemon does not pick up playing where it left off
+	#[serde(skip)]
+	pub state: PlaybackState,
+	pub queue: Vec<QueueEntry>,
+	/// Index into the queue of the track that is playing or paused
+	pub current: Option<usize>,
+	#[serde_as(as = "DurationSecondsWithFrac<f64>")]
+	pub position: Duration,
+	pub volume: u8,
+	pub shuffle: bool,
+	pub repeat: RepeatMode,
+	/// Id of the next entry added to the queue
+	pub next_id: u64,
+}
+
+impl Default for Player {
+	fn default() -> Self {
+		Self {
+			state: PlaybackState::Stopped,
+			queue: Vec::new(),
+			current: None,
+			position: Duration::ZERO,
+			volume: 100,
+			shuffle: false,
+			repeat: RepeatMode::Off,
+			next_id: 0,
+		}
+	}
+}
+
+impl Player {
+	pub fn current_entry(&self) -> Option<QueueEntry> {
+		self.queue.get(self.current?).copied()
+	}
+
+	/// Appends a track to the queue under a new id
+	pub fn push(&mut self, album: usize, track: u32) {
+		let id = self.next_id;
+		self.next_id += 1;
+		self.queue.push(QueueEntry { id, album, track });
+	}
+
+	/// The queue index of the entry with `id`
+	pub fn position_of(&self, id: u64) -> Option<usize> {
+		self.queue.iter().position(|entry| entry.id == id)
+	}
+
+	/// The queue index after the current track, following the shuffle and
+	/// repeat settings
+	pub fn next_index(&self) -> Option<usize> {
+		let (current, len) = (self.current?, self.queue.len());
+		if self.shuffle && len > 1 {
+			let offset = RandomState::new().hash_one(current) as usize % (len - 1);
+			return Some((current + 1 + offset) % len);
+		}
+		match current + 1 {
+			next if next < len => Some(next),
+			_ if self.repeat == RepeatMode::All => Some(0),
+			_ => None,
+		}
+	}
+
+	pub fn previous_index(&self) -> Option<usize> {
+		match self.current? {
+			0 if self.repeat == RepeatMode::All => self.queue.len().checked_sub(1),
+			0 => Some(0),
+			current => Some(current - 1),
+		}
+	}
+
+	/// Makes the track at `index` current and rewinds it, stopping playback
+	/// when there is none
+	pub fn go_to(&mut self, index: Option<usize>) {
+		self.current = index;
+		self.position = Duration::ZERO;
+		if index.is_none() {
+			self.state = PlaybackState::Stopped;
+		}
+	}
+
+	/// Removes the entries in `start..end`. When the current track is among
+	/// them the one after it becomes current
+	pub fn remove(&mut self, start: usize, end: usize) {
+		self.queue.drain(start..end);
+		match self.current {
+			Some(current) if current >= end => self.current = Some(current - (end - start)),
+			Some(current) if current >= start => {
+				self.go_to((start < self.queue.len()).then_some(start));
+			}
+			_ => (),
+		}
+	}
+
+	/// Moves an entry and keeps `current` on the same track
+	pub fn move_entry(&mut self, from: usize, to: usize) {
+		let entry = self.queue.remove(from);
+		self.queue.insert(to, entry);
+		self.current = self.current.map(|current| match current {
+			current if current == from => to,
+			current if from < current && current <= to => current - 1,
+			current if to <= current && current < from => current + 1,
+			current => current,
+		});
+	}
+}
@@ -0,0 +1,347 @@
+use crate::{
+	event::{Push, Topic},
+	execute::{ExecuteError, Reply},
+	script::{OnError, ScriptError, ScriptErrorKind},
+};
+use serde::{Deserialize, Serialize};
+use sonas_macros::CommandValue;
+use sonas_parser::ParseCommandError;
+use std::{fmt, ops::Range};
+
+pub const VERSION: u32 = 1;
+
+/// First line sent by either side of a JSON connection
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Hello {
+	pub hello: String,
+	pub version: u32,
+}
+
+impl Hello {
+	pub fn new(name: &str) -> Self {
+		Self {
+			hello: name.to_string(),
+			version: VERSION,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Request {
+	pub id: u64,
+	#[serde(flatten)]
+	pub body: RequestBody,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum RequestBody {
+	Command {
+		command: String,
+	},
+	Script {
+		script: String,
+		#[serde(default, skip_serializing_if = "Option::is_none")]
+		on_error: Option<OnError>,
+	},
+	Help {
+		#[serde(default)]
+		path: String,
+	},
+	Set(Settings),
+	/// Starts pushing events for the topics, or for all topics when empty
+	Subscribe {
+		#[serde(default)]
+		topics: Vec<Topic>,
+	},
+	/// Stops pushing events for the topics, or for all topics when empty
+	Unsubscribe {
+		#[serde(default)]
+		topics: Vec<Topic>,
+	},
+	/// Stops the daemon once the response is sent
+	Shutdown,
+}
+
+/// The word a plain request starts with when it is not a command
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PlainVerb {
+	Help,
+	Script,
+	Set,
+	Shutdown,
+	Subscribe,
+	Unsubscribe,
+}
+
+impl PlainVerb {
+	pub const ALL: [Self; 6] = [
+		Self::Help,
+		Self::Script,
+		Self::Set,
+		Self::Shutdown,
+		Self::Subscribe,
+		Self::Unsubscribe,
+	];
+
+	pub const fn name(self) -> &'static str {
+		match self {
+			Self::Help => "help",
+			Self::Script => "script",
+			Self::Set => "set",
+			Self::Shutdown => "shutdown",
+			Self::Subscribe => "subscribe",
+			Self::Unsubscribe => "unsubscribe",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|verb| verb.name() == name)
+	}
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default, Serialize, Deserialize, CommandValue)]
+#[serde(rename_all = "kebab-case")]
+pub enum Format {
+	#[default]
+	Plain,
+	Json,
+}
+
+/// Per-connection settings, fields left out are not changed
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default, Serialize, Deserialize)]
+pub struct Settings {
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub format: Option<Format>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub on_error: Option<OnError>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Response {
+	pub id: u64,
+	#[serde(flatten)]
+	pub status: Status,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub results: Vec<Reply>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum Status {
+	Ok,
+	Error { errors: Vec<ErrorPayload> },
+}
+
+impl Response {
+	pub fn new(id: u64, results: Vec<Reply>, errors: Vec<ErrorPayload>) -> Self {
+		let status = match errors.is_empty() {
+			true => Status::Ok,
+			false => Status::Error { errors },
+		};
+		Self {
+			id,
+			status,
+			results,
+		}
+	}
+
+	pub fn error(id: u64, error: ErrorPayload) -> Self {
+		Self::new(id, Vec::new(), vec![error])
+	}
+
+	pub fn is_ok(&self) -> bool {
+		self.status == Status::Ok
+	}
+}
+
+/// Renders the results followed by the error diagnostics, as in plain-text mode
+impl fmt::Display for Response {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		for result in &self.results {
+			write!(f, "{result}")?;
+		}
+		match &self.status {
+			Status::Ok => Ok(()),
+			Status::Error { errors } => errors.iter().try_for_each(|error| write!(f, "{error}")),
+		}
+	}
+}
+
+/// Any line sent by sonasd after the hello
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ServerMessage {
+	Push(Push),
+	Response(Response),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorKind {
+	Protocol,
+	Version,
+	Parse,
+	Alias,
+	Execute,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ErrorPayload {
+	pub kind: ErrorKind,
+	pub message: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub line: Option<usize>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub span: Option<Range<usize>>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub suggestion: Option<String>,
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub path: Vec<String>,
+	pub diagnostic: String,
+}
+
+impl ErrorPayload {
+	pub fn new(kind: ErrorKind, message: impl fmt::Display) -> Self {
+		Self {
+			kind,
+			diagnostic: format!("error: {message}\n"),
+			message: message.to_string(),
+			line: None,
+			span: None,
+			suggestion: None,
+			path: Vec::new(),
+		}
+	}
+
+	pub fn parse(error: &ParseCommandError, input: &str) -> Self {
+		Self {
+			span: Some(error.span.clone()),
+			suggestion: error.suggestion.clone(),
+			path: error.path.clone(),
+			diagnostic: error.diagnostic(input),
+			..Self::new(ErrorKind::Parse, error)
+		}
+	}
+
+	pub fn script(error: &ScriptError) -> Self {
+		match &error.kind {
+			ScriptErrorKind::Parse(parse) => Self::parse(parse, &error.text),
+			ScriptErrorKind::Alias(alias) => Self::new(ErrorKind::Alias, alias),
+		}
+		.at_line(error.line)
+	}
+
+	pub fn at_line(self, line: usize) -> Self {
+		Self {
+			line: Some(line),
+			diagnostic: format!("line {line}: {}", self.diagnostic),
+			..self
+		}
+	}
+}
+
+impl From<&ExecuteError> for ErrorPayload {
+	fn from(error: &ExecuteError) -> Self {
+		Self::new(ErrorKind::Execute, error)
+	}
+}
+
+impl fmt::Display for ErrorPayload {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(&self.diagnostic)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Command;
+
+	#[test]
+	fn requests_round_trip() {
+		let request = Request {
+			id: 7,
+			body: RequestBody::Script {
+				script: "album list".to_string(),
+				on_error: Some(OnError::Continue),
+			},
+		};
+		let json = serde_json::to_string(&request).unwrap();
+
+		assert_eq!(
+			json,
+			r#"{"id":7,"type":"script","script":"album list","on_error":"continue"}"#
+		);
+		assert_eq!(serde_json::from_str::<Request>(&json).unwrap(), request);
+		assert_eq!(
+			serde_json::from_str::<Request>(r#"{"id":1,"type":"help"}"#).unwrap(),
+			Request {
+				id: 1,
+				body: RequestBody::Help {
+					path: String::new()
+				},
+			}
+		);
+		assert_eq!(
+			serde_json::to_string(&Request {
+				id: 3,
+				body: RequestBody::Shutdown
+			})
+			.unwrap(),
+			r#"{"id":3,"type":"shutdown"}"#
+		);
+		assert_eq!(
+			serde_json::from_str::<Request>(r#"{"id":2,"type":"set","format":"plain"}"#).unwrap(),
+			Request {
+				id: 2,
+				body: RequestBody::Set(Settings {
+					format: Some(Format::Plain),
+					on_error: None,
+				}),
+			}
+		);
+	}
+
+	#[test]
+	fn responses_round_trip() {
+		let response = Response::new(3, vec![Reply::Ack], Vec::new());
+		let json = serde_json::to_string(&response).unwrap();
+
+		assert_eq!(json, r#"{"id":3,"status":"ok","results":[{"type":"ack"}]}"#);
+		assert_eq!(serde_json::from_str::<Response>(&json).unwrap(), response);
+
+		let input = "album lst";
+		let error = input.parse::<Command>().unwrap_err();
+		let response = Response::error(4, ErrorPayload::parse(&error, input));
+		let json = serde_json::to_string(&response).unwrap();
+
+		assert!(json.starts_with(
+			r#"{"id":4,"status":"error","errors":[{"kind":"parse","message":"unknown subcommand 'lst' (in 'album')","span":{"start":6,"end":9},"suggestion":"list","path":["album"],"#
+		));
+		assert_eq!(serde_json::from_str::<Response>(&json).unwrap(), response);
+	}
+
+	#[test]
+	fn pushes_are_told_apart_from_responses() {
+		let message = serde_json::from_str::<ServerMessage>(r#"{"lagged":{"missed":1}}"#);
+		assert_eq!(
+			message.unwrap(),
+			ServerMessage::Push(Push::Lagged { missed: 1 })
+		);
+
+		let message = serde_json::from_str::<ServerMessage>(r#"{"id":1,"status":"ok"}"#);
+		assert_eq!(
+			message.unwrap(),
+			ServerMessage::Response(Response::new(1, Vec::new(), Vec::new()))
+		);
+	}
+
+	#[test]
+	fn errors_render_diagnostics() {
+		let error = ErrorPayload::from(&ExecuteError::UnknownAlbum(3)).at_line(2);
+
+		assert_eq!(error.line, Some(2));
+		assert_eq!(error.to_string(), "line 2: error: no album with id 3\n");
+	}
+}
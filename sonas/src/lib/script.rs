@@ -0,0 +1,350 @@
+use crate::{
+	Command,
+	alias::{AliasError, AliasTable},
+};
+use serde::{Deserialize, Serialize};
+use sonas_macros::CommandValue;
+use sonas_parser::ParseCommandError;
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default, Serialize, Deserialize, CommandValue)]
+#[serde(rename_all = "kebab-case")]
+pub enum OnError {
+	#[default]
+	Stop,
+	Continue,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Statement {
+	pub line: usize,
+	pub text: String,
+	breaks: Vec<(usize, usize)>,
+}
+
+impl Statement {
+	fn new(line: usize) -> Self {
+		Self {
+			line,
+			text: String::new(),
+			breaks: vec![(0, line)],
+		}
+	}
+
+	pub fn line_at(&self, offset: usize) -> usize {
+		self.breaks
+			.iter()
+			.take_while(|&&(start, _)| start <= offset)
+			.last()
+			.map_or(self.line, |&(_, line)| line)
+	}
+
+	pub fn parse(&self) -> Result<Command, ScriptError> {
+		self.text
+			.parse()
+			.map_err(|error: ParseCommandError| ScriptError {
+				line: self.line_at(error.span.start),
+				text: self.text.clone(),
+				kind: ScriptErrorKind::Parse(Box::new(error)),
+			})
+	}
+
+	pub fn expand(&self, aliases: &AliasTable) -> Vec<Result<Command, ScriptError>> {
+		let texts = match aliases.expand(&self.text) {
+			Ok(texts) => texts,
+			Err(error) => {
+				return vec![Err(ScriptError {
+					line: self.line,
+					text: self.text.clone(),
+					kind: ScriptErrorKind::Alias(error),
+				})];
+			}
+		};
+
+		texts
+			.into_iter()
+			.map(|text| {
+				if text == self.text {
+					self.parse()
+				} else {
+					Self {
+						text,
+						..Self::new(self.line)
+					}
+					.parse()
+				}
+			})
+			.collect()
+	}
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Error)]
+#[error("line {line}: {kind}")]
+pub struct ScriptError {
+	pub line: usize,
+	pub text: String,
+	pub kind: ScriptErrorKind,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Error)]
+pub enum ScriptErrorKind {
+	#[error(transparent)]
+	Parse(Box<ParseCommandError>),
+	#[error(transparent)]
+	Alias(AliasError),
+}
+
+impl ScriptError {
+	pub fn diagnostic(&self) -> String {
+		match &self.kind {
+			ScriptErrorKind::Parse(error) => {
+				format!("line {}: {}", self.line, error.diagnostic(&self.text))
+			}
+			ScriptErrorKind::Alias(error) => {
+				format!("line {}: error: {error}\n  | {}\n", self.line, self.text)
+			}
+		}
+	}
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+pub struct Script {
+	pub statements: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Quote {
+	None,
+	Single,
+	Double,
+}
+
+impl Script {
+	pub fn parse(input: &str) -> Self {
+		let mut statements = Vec::new();
+		let mut line = 1;
+		let mut statement = Statement::new(line);
+		let mut quote = Quote::None;
+		let mut chars = input.chars().peekable();
+
+		let mut finish = |statement: &mut Statement, next_line: usize| {
+			let finished = std::mem::replace(statement, Statement::new(next_line));
+			if !finished.text.trim().is_empty() {
+				statements.push(finished);
+			}
+		};
+
+		while let Some(c) = chars.next() {
+			match (quote, c) {
+				(_, '\n') => {
+					line += 1;
+					quote = Quote::None;
+					finish(&mut statement, line);
+				}
+				(Quote::None | Quote::Double, '\\') if chars.peek() == Some(&'\n') => {
+					chars.next();
+					line += 1;
+					statement.text.push(' ');
+					statement.breaks.push((statement.text.len(), line));
+				}
+				(Quote::None | Quote::Double, '\\') => {
+					statement.text.push(c);
+					if let Some(escaped) = chars.next_if(|&c| c != '\n') {
+						statement.text.push(escaped);
+					}
+				}
+				(Quote::None, ';') => finish(&mut statement, line),
+				(Quote::None, '#')
+					if statement
+						.text
+						.chars()
+						.next_back()
+						.is_none_or(char::is_whitespace) =>
+				{
+					while chars.next_if(|&c| c != '\n').is_some() {}
+				}
+				(Quote::None, '\'') => {
+					quote = Quote::Single;
+					statement.text.push(c);
+				}
+				(Quote::None, '"') => {
+					quote = Quote::Double;
+					statement.text.push(c);
+				}
+				(Quote::Single, '\'') | (Quote::Double, '"') => {
+					quote = Quote::None;
+					statement.text.push(c);
+				}
+				(_, c) => statement.text.push(c),
+			}
+		}
+		finish(&mut statement, line);
+
+		Self { statements }
+	}
+
+	pub fn commands(
+		&self,
+		aliases: &AliasTable,
+	) -> impl Iterator<Item = (usize, Result<Command, ScriptError>)> {
+		self.statements.iter().flat_map(|statement| {
+			statement
+				.expand(aliases)
+				.into_iter()
+				.map(|command| (statement.line, command))
+		})
+	}
+
+	pub fn run(
+		&self,
+		aliases: &AliasTable,
+		on_error: OnError,
+		mut execute: impl FnMut(usize, Result<Command, ScriptError>) -> bool,
+	) -> bool {
+		let mut success = true;
+		for (line, command) in self.commands(aliases) {
+			if !execute(line, command) {
+				success = false;
+				if on_error == OnError::Stop {
+					break;
+				}
+			}
+		}
+		success
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{AlbumCommand, SortDirection};
+	use sonas_parser::ParseCommandErrorKind;
+
+	fn texts(script: &Script) -> Vec<(usize, &str)> {
+		script
+			.statements
+			.iter()
+			.map(|statement| (statement.line, statement.text.trim()))
+			.collect()
+	}
+
+	#[test]
+	fn splits_statements() {
+		let script = Script::parse(
+			"# list everything\n\
+			album list; album list-tracks 3\n\
+			\n\
+			album list \\\n\
+			\tsort=asc # oldest first\n\
+			album list-tracks '#4;5'\n",
+		);
+
+		assert_eq!(
+			texts(&script),
+			vec![
+				(2, "album list"),
+				(2, "album list-tracks 3"),
+				(4, "album list  \tsort=asc"),
+				(6, "album list-tracks '#4;5'"),
+			]
+		);
+	}
+
+	#[test]
+	fn parses_commands() {
+		let script = Script::parse("album list sort=a\nalbum list-tracks 3");
+		let commands = script
+			.commands(&AliasTable::default())
+			.map(|(line, command)| (line, command.unwrap()))
+			.collect::<Vec<_>>();
+
+		assert_eq!(
+			commands,
+			vec![
+				(
+					1,
+					Command::Album(AlbumCommand::List {
+						sort: SortDirection::Ascending
+					})
+				),
+				(2, Command::Album(AlbumCommand::ListTracks { id: 3 })),
+			]
+		);
+	}
+
+	#[test]
+	fn errors_report_line_numbers() {
+		let script = Script::parse("album list\n\nalbum list \\\n  srot=asc");
+		let error = script.statements[1].parse().unwrap_err();
+
+		assert_eq!(error.line, 4);
+		assert_eq!(
+			error.kind,
+			ScriptErrorKind::Parse(Box::new(
+				ParseCommandErrorKind::UnexpectedArgument("srot".to_string())
+					.at(14..18)
+					.with_suggestion(Some("sort"))
+					.within_path(&["album", "list"])
+			))
+		);
+		assert!(
+			error
+				.diagnostic()
+				.starts_with("line 4: error: unexpected argument")
+		);
+	}
+
+	#[test]
+	fn expands_aliases() {
+		let aliases = toml_aliases(
+			"newest = \"album list sort=desc; tracks 1\"\ntracks = \"album list-tracks $1\"",
+		);
+		let script = Script::parse("album list-tracks 2\nnewest\ntracks");
+		let commands = script.commands(&aliases).collect::<Vec<_>>();
+
+		assert_eq!(commands.len(), 4);
+		assert_eq!(
+			commands[2],
+			(2, Ok(Command::Album(AlbumCommand::ListTracks { id: 1 })))
+		);
+		assert!(matches!(
+			&commands[3],
+			(
+				3,
+				Err(ScriptError {
+					kind: ScriptErrorKind::Alias(_),
+					..
+				})
+			)
+		));
+	}
+
+	fn toml_aliases(toml: &str) -> AliasTable {
+		::config::Config::builder()
+			.add_source(::config::File::from_str(toml, ::config::FileFormat::Toml))
+			.build()
+			.and_then(|config| config.try_deserialize())
+			.unwrap()
+	}
+
+	#[test]
+	fn stops_or_continues_on_error() {
+		let script = Script::parse("album list; albm list; album list-tracks 1");
+
+		let mut lines = 0;
+		let success = script.run(&AliasTable::default(), OnError::Stop, |_, command| {
+			lines += 1;
+			command.is_ok()
+		});
+		assert!(!success);
+		assert_eq!(lines, 2);
+
+		let mut lines = 0;
+		let success = script.run(&AliasTable::default(), OnError::Continue, |_, command| {
+			lines += 1;
+			command.is_ok()
+		});
+		assert!(!success);
+		assert_eq!(lines, 3);
+	}
+}
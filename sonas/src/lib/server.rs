@@ -1,27 +1,190 @@
-use interprocess::local_socket::{GenericNamespaced, Name, Stream, ToNsName, prelude::*};
-use std::io::{self, BufRead, BufReader, Write};
+use crate::{
+	event::Push,
+	instance::Instance,
+	protocol::{Hello, Request, RequestBody, Response, ServerMessage, VERSION},
+};
+use interprocess::local_socket::{
+	Name, Stream,
+	prelude::*,
+	tokio::{self as async_socket, RecvHalf, SendHalf, prelude::*},
+};
+use serde::{Serialize, de::DeserializeOwned};
+use std::{
+	collections::VecDeque,
+	io::{self, BufRead, BufReader, Write},
+};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader as AsyncBufReader, Lines};
 
-const NAME: &str = "sonasd.sock";
+/// A JSON session with sonasd that can be reused for many requests
+#[derive(Debug)]
+pub struct Client {
+	connection: BufReader<Stream>,
+	server: Hello,
+	next_id: u64,
+	pushes: VecDeque<Push>,
+}
+
+impl Client {
+	/// Connects to the instance picked by the environment or the config file
+	pub fn connect() -> io::Result<Self> {
+		Self::connect_to(Instance::resolve(None, None)?.name()?)
+	}
+
+	pub fn connect_to(name: Name) -> io::Result<Self> {
+		let mut connection = BufReader::new(Stream::connect(name)?);
+
+		write_json(connection.get_mut(), &Hello::new(env!("CARGO_PKG_NAME")))?;
+		let server = check_version(read_json(&mut connection)?)?;
+
+		Ok(Self {
+			connection,
+			server,
+			next_id: 1,
+			pushes: VecDeque::new(),
+		})
+	}
+
+	pub fn server(&self) -> &Hello {
+		&self.server
+	}
 
-pub fn send_bytes(data: &[u8]) -> io::Result<String> {
-	let name = name()?;
+	/// Sends a request without waiting for its response, returning the request id
+	pub fn send(&mut self, body: RequestBody) -> io::Result<u64> {
+		let id = self.next_id;
+		self.next_id += 1;
 
-	let mut connection = BufReader::new(Stream::connect(name)?);
-	connection.get_mut().write_all(data)?;
+		write_json(self.connection.get_mut(), &Request { id, body })?;
+		Ok(id)
+	}
 
-	let mut buffer = String::new();
-	connection.read_line(&mut buffer)?;
+	/// Reads the next response, in the order the requests were sent. Events
+	/// arriving in the meantime are kept for [`Client::next_push`]
+	pub fn receive(&mut self) -> io::Result<Response> {
+		loop {
+			match read_json(&mut self.connection)? {
+				ServerMessage::Response(response) => return Ok(response),
+				ServerMessage::Push(push) => self.pushes.push_back(push),
+			}
+		}
+	}
 
-	Ok(buffer)
+	/// Waits for the next event on a subscribed connection
+	pub fn next_push(&mut self) -> io::Result<Push> {
+		if let Some(push) = self.pushes.pop_front() {
+			return Ok(push);
+		}
+
+		match read_json(&mut self.connection)? {
+			ServerMessage::Push(push) => Ok(push),
+			ServerMessage::Response(response) => Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("unexpected response to request {}", response.id),
+			)),
+		}
+	}
+
+	pub fn request(&mut self, body: RequestBody) -> io::Result<Response> {
+		let id = self.send(body)?;
+		let response = self.receive()?;
+		if response.id != id {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("expected response to request {id}, got {}", response.id),
+			));
+		}
+		Ok(response)
+	}
+}
+
+/// A JSON session with sonasd for async clients, which receive responses and
+/// pushes in one stream as they arrive
+#[derive(Debug)]
+pub struct AsyncClient {
+	lines: Lines<AsyncBufReader<RecvHalf>>,
+	sender: SendHalf,
+	server: Hello,
+	next_id: u64,
 }
 
-pub fn send_line(line: &str) -> io::Result<String> {
-	let value = format!("{line}\n");
-	let bytes = value.into_bytes();
+impl AsyncClient {
+	pub async fn connect(name: Name<'_>) -> io::Result<Self> {
+		let (recver, mut sender) = async_socket::Stream::connect(name).await?.split();
+		let mut lines = AsyncBufReader::new(recver).lines();
+
+		write_json_async(&mut sender, &Hello::new(env!("CARGO_PKG_NAME"))).await?;
+		let server = check_version(read_json_async(&mut lines).await?)?;
+
+		Ok(Self {
+			lines,
+			sender,
+			server,
+			next_id: 1,
+		})
+	}
+
+	pub fn server(&self) -> &Hello {
+		&self.server
+	}
+
+	/// Sends a request, returning the id its response will carry
+	pub async fn send(&mut self, body: RequestBody) -> io::Result<u64> {
+		let id = self.next_id;
+		self.next_id += 1;
+
+		write_json_async(&mut self.sender, &Request { id, body }).await?;
+		Ok(id)
+	}
+
+	/// Waits for the next response or push, this is cancel safe
+	pub async fn receive(&mut self) -> io::Result<ServerMessage> {
+		read_json_async(&mut self.lines).await
+	}
+}
+
+fn check_version(server: Hello) -> io::Result<Hello> {
+	match server.version == VERSION {
+		true => Ok(server),
+		false => Err(io::Error::new(
+			io::ErrorKind::InvalidData,
+			format!(
+				"{} speaks protocol version {}, expected {VERSION}",
+				server.hello, server.version
+			),
+		)),
+	}
+}
+
+async fn write_json_async(sender: &mut SendHalf, value: &impl Serialize) -> io::Result<()> {
+	let mut line = serde_json::to_string(value)?;
+	line.push('\n');
+	sender.write_all(line.as_bytes()).await
+}
+
+async fn read_json_async<T: DeserializeOwned>(
+	lines: &mut Lines<AsyncBufReader<RecvHalf>>,
+) -> io::Result<T> {
+	match lines.next_line().await? {
+		Some(line) => Ok(serde_json::from_str(&line)?),
+		None => Err(io::Error::new(
+			io::ErrorKind::UnexpectedEof,
+			"connection closed by sonasd",
+		)),
+	}
+}
 
-	send_bytes(&bytes)
+pub fn write_json(writer: &mut impl Write, value: &impl Serialize) -> io::Result<()> {
+	let mut line = serde_json::to_string(value)?;
+	line.push('\n');
+	writer.write_all(line.as_bytes())
 }
 
-pub fn name() -> io::Result<Name<'static>> {
-	NAME.to_ns_name::<GenericNamespaced>()
+pub fn read_json<T: DeserializeOwned>(reader: &mut impl BufRead) -> io::Result<T> {
+	let mut line = String::new();
+	if reader.read_line(&mut line)? == 0 {
+		return Err(io::Error::new(
+			io::ErrorKind::UnexpectedEof,
+			"connection closed by sonasd",
+		));
+	}
+	Ok(serde_json::from_str(&line)?)
 }
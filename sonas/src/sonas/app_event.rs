@@ -1,4 +1,5 @@
 use oprabeli::ratatui::layout::Rect;
+use sonas::{event::Event, protocol::Response};
 
 use crate::util::QuadDirection;
 
@@ -17,5 +18,10 @@ pub enum AppEvent {
 	},
 	ScrollTo(Rect),
 	TestError(String),
+	RunCommand(String),
 	UpdateKeymap,
+	DaemonConnected,
+	DaemonDisconnected(String),
+	DaemonReply(Response),
+	DaemonEvent(Event),
 }
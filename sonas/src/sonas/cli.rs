@@ -2,13 +2,33 @@ use std::path::PathBuf;
 
 use clap::Parser;
 use directories::ProjectDirs;
+use sonas::{
+	instance::{self, Instance, InvalidInstanceError},
+	paths,
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version = version(), about)]
-pub struct Args {}
+pub struct Args {
+	/// Socket of sonasd, either a namespaced name or a file path [env: SONAS_SOCKET]
+	#[arg(long, value_name = "PATH")]
+	socket: Option<String>,
+
+	/// Name of the sonasd instance to connect to [env: SONAS_INSTANCE]
+	#[arg(
+		long,
+		value_name = "NAME",
+		value_parser = instance::parse_name,
+		conflicts_with = "socket"
+	)]
+	instance: Option<String>,
+
+	/// Do not start sonasd when it is not running
+	#[arg(long)]
+	pub no_spawn: bool,
+}
 
 pub struct Cli {
-	#[allow(dead_code)]
 	pub args: Args,
 	pub proj_dirs: Option<ProjectDirs>,
 }
@@ -16,12 +36,21 @@ pub struct Cli {
 impl Cli {
 	pub fn new() -> Self {
 		let args = Args::parse();
-		let proj_dirs = ProjectDirs::from("net", "LunaPresent", "sonas");
+		let proj_dirs = paths::project_dirs();
 		Self { args, proj_dirs }
 	}
 
+	pub fn instance(&self) -> Result<Instance, InvalidInstanceError> {
+		Instance::resolve(self.args.socket.clone(), self.args.instance.clone())
+	}
+
 	pub fn config_path(&self) -> Option<PathBuf> {
-		Some(self.proj_dirs.as_ref()?.config_dir().join("config"))
+		Some(
+			self.proj_dirs
+				.as_ref()?
+				.config_dir()
+				.join(paths::CONFIG_FILE),
+		)
 	}
 }
 
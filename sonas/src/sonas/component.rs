@@ -1,5 +1,6 @@
 mod album_card;
 mod control_panel;
+mod daemon;
 mod error_popup;
 mod error_reporter;
 mod fps;
@@ -9,6 +10,7 @@ mod navbar_button;
 mod root;
 mod scrollable;
 
+pub use daemon::{Daemon, DaemonComponent};
 pub use error_reporter::ErrorReporterComponent;
 pub use fps::FpsComponent;
 pub use root::RootComponent;
@@ -7,9 +7,18 @@ use oprabeli::ecs::*;
 use oprabeli::event::SystemEvent;
 use oprabeli::ratatui::layout::{Constraint, Flex, Layout};
 use oprabeli::ratatui::style::Stylize as _;
+use oprabeli::ratatui::text::Line;
 use oprabeli::ratatui::widgets::{Block, Widget};
 
-use crate::config::Theme;
+use sonas::{
+	Command, PlayerCommand,
+	event::{Event, PlaybackState},
+	execute::Reply,
+	protocol::{RequestBody, Response},
+};
+
+use super::Daemon;
+use crate::{app_event::AppEvent, config::Theme};
 
 #[derive(Debug, Component, Default, Clone, Copy)]
 #[component(on_add = Self::register_systems)]
@@ -20,7 +29,11 @@ pub struct ControlPanelComponent {
 
 impl UiComponent for ControlPanelComponent {
 	fn systems() -> impl IntoIterator<Item = UiSystem> {
-		[UiSystem::new(Self::update), UiSystem::new(Self::render)]
+		[
+			UiSystem::new(Self::update),
+			UiSystem::new(Self::handle_daemon),
+			UiSystem::new(Self::render),
+		]
 	}
 }
 
@@ -29,16 +42,14 @@ impl ControlPanelComponent {
 		if self.playing { "󰏤" } else { "󰐊" }
 	}
 
-	fn update(
-		context: EventContext<SystemEvent>,
-		mut query: Query<&mut Self>,
-	) -> eyre::Result<EventFlow> {
-		let mut comp = query.get_mut(context.entity)?;
-
+	fn update(context: EventContext<SystemEvent>, daemon: Res<Daemon>) -> eyre::Result<EventFlow> {
 		Ok(match context.event {
 			SystemEvent::Mouse(mouse_event) => match mouse_event.kind {
 				MouseEventKind::Down(MouseButton::Left) => {
-					comp.playing = !comp.playing;
+					// the icon changes once sonasd reports the new state
+					daemon.send(RequestBody::Command {
+						command: Command::from(PlayerCommand::Toggle).to_string(),
+					})?;
 					EventFlow::Consume
 				}
 				_ => EventFlow::Propagate,
@@ -47,9 +58,32 @@ impl ControlPanelComponent {
 		})
 	}
 
+	fn handle_daemon(
+		context: EventContext<AppEvent>,
+		mut query: Query<&mut Self>,
+	) -> eyre::Result<EventFlow> {
+		let mut comp = query.get_mut(context.entity)?;
+
+		match context.event {
+			AppEvent::DaemonEvent(Event::PlayerState { state }) => {
+				comp.playing = *state == PlaybackState::Playing;
+			}
+			AppEvent::DaemonReply(Response { results, .. }) => {
+				for result in results {
+					if let Reply::Status(status) = result {
+						comp.playing = status.state == PlaybackState::Playing;
+					}
+				}
+			}
+			_ => (),
+		}
+		Ok(EventFlow::Propagate)
+	}
+
 	fn render(
 		context: RenderContext,
 		theme: Res<Theme>,
+		daemon: Option<Res<Daemon>>,
 		query: Query<(&Self, &Area)>,
 	) -> eyre::Result<()> {
 		let (comp, area) = query.get(context.entity)?;
@@ -62,6 +96,14 @@ impl ControlPanelComponent {
 		let [button_area] = Layout::vertical([Constraint::Length(1)])
 			.flex(Flex::Center)
 			.areas(area);
+
+		if !daemon.is_some_and(|daemon| daemon.is_connected()) {
+			Line::from("Disconnected from sonasd, reconnecting…")
+				.fg(theme.colours.border_error)
+				.centered()
+				.render(button_area, context.buffer);
+			return Ok(());
+		}
 		let [button_area] = Layout::horizontal([Constraint::Length(2)])
 			.flex(Flex::Center)
 			.areas(button_area);
@@ -0,0 +1,208 @@
+use core::time::Duration;
+use std::io;
+
+use color_eyre::eyre;
+use oprabeli::bevy_ecs;
+use oprabeli::bevy_ecs::component::Component;
+use oprabeli::bevy_ecs::resource::Resource;
+use oprabeli::bevy_ecs::system::{Commands, Query, Res, ResMut};
+use oprabeli::ecs::*;
+use oprabeli::event::DispatchMethod;
+use sonas::{
+	Command, PlayerCommand,
+	event::Push,
+	instance::{self, Instance},
+	protocol::{RequestBody, Response, ServerMessage, Status},
+	server::AsyncClient,
+};
+use tokio::{
+	sync::mpsc,
+	task::{self, JoinHandle},
+	time,
+};
+
+use crate::app_event::AppEvent;
+
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Sends requests to sonasd, the responses arrive as [`AppEvent::DaemonReply`]
+#[derive(Debug, Resource)]
+pub struct Daemon {
+	requests: mpsc::UnboundedSender<RequestBody>,
+	connected: bool,
+}
+
+impl Daemon {
+	pub fn is_connected(&self) -> bool {
+		self.connected
+	}
+
+	pub fn send(&self, body: RequestBody) -> eyre::Result<()> {
+		if !self.connected {
+			eyre::bail!("not connected to sonasd");
+		}
+		self.requests
+			.send(body)
+			.map_err(|_| eyre::eyre!("connection to sonasd was closed"))
+	}
+}
+
+/// Keeps a connection to sonasd open, reconnecting whenever it is lost
+#[derive(Debug, Component)]
+#[component(on_add = Self::register_systems)]
+#[component(on_remove = Self::unregister_systems)]
+pub struct DaemonComponent {
+	instance: Instance,
+	spawn: bool,
+	task: Option<JoinHandle<()>>,
+}
+
+impl UiComponent for DaemonComponent {
+	fn systems() -> impl IntoIterator<Item = UiSystem> {
+		[UiSystem::new(Self::init), UiSystem::new(Self::update)]
+	}
+}
+
+impl Drop for DaemonComponent {
+	fn drop(&mut self) {
+		if let Some(task) = &self.task {
+			task.abort();
+		}
+	}
+}
+
+impl DaemonComponent {
+	/// With `spawn` set sonasd is started when it is not running yet
+	pub fn new(instance: Instance, spawn: bool) -> Self {
+		Self {
+			instance,
+			spawn,
+			task: None,
+		}
+	}
+
+	fn init(
+		context: InitContext,
+		async_events: Res<AsyncEventQueue>,
+		mut query: Query<&mut Self>,
+		mut cmd: Commands,
+	) -> eyre::Result<()> {
+		let mut comp = query.get_mut(context.entity)?;
+		let (requests, receiver) = mpsc::unbounded_channel();
+
+		cmd.insert_resource(Daemon {
+			requests,
+			connected: false,
+		});
+		comp.task = Some(tokio::spawn(run(
+			comp.instance.clone(),
+			comp.spawn,
+			receiver,
+			async_events.clone(),
+		)));
+
+		Ok(())
+	}
+
+	fn update(
+		context: EventContext<AppEvent>,
+		mut daemon: ResMut<Daemon>,
+	) -> eyre::Result<EventFlow> {
+		match context.event {
+			AppEvent::DaemonConnected => daemon.connected = true,
+			AppEvent::DaemonDisconnected(_) => daemon.connected = false,
+			AppEvent::DaemonReply(Response {
+				status: Status::Error { errors },
+				..
+			}) => {
+				let messages = errors.iter().map(|error| error.message.as_str());
+				eyre::bail!(messages.collect::<Vec<_>>().join("\n"));
+			}
+			_ => (),
+		}
+		Ok(EventFlow::Propagate)
+	}
+}
+
+async fn run(
+	instance: Instance,
+	mut spawn: bool,
+	mut requests: mpsc::UnboundedReceiver<RequestBody>,
+	mut events: AsyncEventQueue,
+) {
+	let mut connected = None;
+	loop {
+		let session = session(&instance, spawn, &mut requests, &mut events, &mut connected);
+		let error = match session.await {
+			Ok(()) => return,
+			Err(e) => e,
+		};
+		// only start sonasd once, a daemon that goes away later was most
+		// likely stopped on purpose
+		spawn = false;
+		if connected != Some(false) {
+			connected = Some(false);
+			events.send(
+				DispatchMethod::Broadcast,
+				AppEvent::DaemonDisconnected(error.to_string()),
+			);
+		}
+
+		time::sleep(RECONNECT_INTERVAL).await;
+		// whatever was sent in the meantime has already been reported as failed
+		while requests.try_recv().is_ok() {}
+	}
+}
+
+async fn session(
+	instance: &Instance,
+	spawn: bool,
+	requests: &mut mpsc::UnboundedReceiver<RequestBody>,
+	events: &mut AsyncEventQueue,
+	connected: &mut Option<bool>,
+) -> io::Result<()> {
+	let mut client = match AsyncClient::connect(instance.name()?).await {
+		Err(e) if spawn && instance::is_not_running(&e) => {
+			let spawned = instance.clone();
+			task::spawn_blocking(move || spawned.spawn()).await??;
+			AsyncClient::connect(instance.name()?).await?
+		}
+		client => client?,
+	};
+	client
+		.send(RequestBody::Subscribe { topics: Vec::new() })
+		.await?;
+	client.send(status_request()).await?;
+	*connected = Some(true);
+	events.send(DispatchMethod::Broadcast, AppEvent::DaemonConnected);
+
+	loop {
+		tokio::select! {
+			message = client.receive() => match message? {
+				ServerMessage::Push(Push::Event(event)) => {
+					events.send(DispatchMethod::Broadcast, AppEvent::DaemonEvent(event));
+				}
+				// the missed events are gone, so ask for the whole state instead
+				ServerMessage::Push(Push::Lagged { .. }) => {
+					client.send(status_request()).await?;
+				}
+				ServerMessage::Response(response) => {
+					events.send(DispatchMethod::Broadcast, AppEvent::DaemonReply(response));
+				}
+			},
+			body = requests.recv() => match body {
+				Some(body) => {
+					client.send(body).await?;
+				}
+				None => return Ok(()),
+			},
+		}
+	}
+}
+
+/// Asks for the player status, the reply brings the components up to date
+fn status_request() -> RequestBody {
+	RequestBody::Command {
+		command: Command::from(PlayerCommand::Status).to_string(),
+	}
+}
@@ -10,13 +10,14 @@ use oprabeli::ratatui::style::Stylize;
 use oprabeli::ratatui::widgets::{Block, Widget};
 
 use super::{
-	ControlPanelComponent, ErrorReporterComponent, LibraryComponent, NavbarComponent,
+	ControlPanelComponent, Daemon, ErrorReporterComponent, LibraryComponent, NavbarComponent,
 	ScrollableComponent,
 };
 use crate::{
 	app_event::AppEvent,
-	config::{Keys, Theme},
+	config::{Aliases, Keys, Theme},
 };
+use sonas::{Command, protocol::RequestBody, script::OnError};
 
 #[derive(Debug, Component)]
 #[component(on_add = Self::register_systems)]
@@ -73,7 +74,9 @@ impl RootComponent {
 
 	fn update(
 		context: EventContext<AppEvent>,
+		aliases: Res<Aliases>,
 		key_config: Res<Keys>,
+		daemon: Res<Daemon>,
 		mut signal: ResMut<Signal>,
 		mut key_handler_query: Query<&mut KeyHandler<AppEvent>>,
 	) -> eyre::Result<EventFlow> {
@@ -91,6 +94,19 @@ impl RootComponent {
 				*key_handler = KeyHandler::new(key_config.generate_key_map());
 				EventFlow::Propagate
 			}
+			AppEvent::RunCommand(input) => {
+				let commands = aliases.expand(input)?;
+				for command in &commands {
+					if let Err(error) = command.parse::<Command>() {
+						eyre::bail!("{}", error.diagnostic(command).trim_end());
+					}
+				}
+				daemon.send(RequestBody::Script {
+					script: commands.join("\n"),
+					on_error: Some(OnError::Stop),
+				})?;
+				EventFlow::Consume
+			}
 			_ => EventFlow::Propagate,
 		})
 	}
@@ -1,9 +1,11 @@
+mod aliases;
 mod config_manager;
 mod input_action;
 mod keys;
 mod settings;
 mod theme;
 
+pub use aliases::Aliases;
 pub use config_manager::ConfigManager;
 pub use keys::Keys;
 pub use settings::Settings;
@@ -14,6 +16,8 @@ use serde::Deserialize;
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 struct AppConfig {
+	#[serde(default)]
+	aliases: Aliases,
 	keys: Keys,
 	theme: Theme,
 	settings: Settings,
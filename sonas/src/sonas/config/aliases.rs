@@ -0,0 +1,8 @@
+use derive_more::Deref;
+use oprabeli::bevy_ecs;
+use oprabeli::bevy_ecs::resource::Resource;
+use serde::Deserialize;
+use sonas::alias::AliasTable;
+
+#[derive(Debug, Default, Deserialize, Resource, Deref)]
+pub struct Aliases(AliasTable);
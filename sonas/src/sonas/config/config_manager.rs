@@ -8,9 +8,13 @@ use oprabeli::bevy_ecs::component::Component;
 use oprabeli::bevy_ecs::system::{Commands, Query, Res, ResMut};
 use oprabeli::ecs::*;
 use oprabeli::event::DispatchMethod;
+use sonas::Command;
+use sonas::alias::AliasError;
+use sonas_parser::ParseCommandError;
 use thiserror::Error;
 
-use super::{AppConfig, Keys, Settings, Theme};
+use super::input_action::InputAction;
+use super::{Aliases, AppConfig, Keys, Settings, Theme};
 use crate::app_event::AppEvent;
 
 #[derive(Debug, Error)]
@@ -21,6 +25,14 @@ pub enum ConfigManagerError {
 	FailedToParse(#[from] ConfigError),
 	#[error("failed to convert config file path to string")]
 	InvalidPath,
+	#[error("invalid command aliases")]
+	InvalidAliases(#[from] AliasError),
+	#[error("invalid command '{command}' bound to a key")]
+	InvalidKeyCommand {
+		command: String,
+		#[source]
+		error: Box<ParseCommandError>,
+	},
 }
 
 #[derive(Debug, Component)]
@@ -57,6 +69,7 @@ impl ConfigManager {
 
 		let config = comp.parse_config()?;
 
+		cmd.insert_resource(config.aliases);
 		cmd.insert_resource(config.keys);
 		cmd.insert_resource(config.theme);
 		cmd.insert_resource(config.settings);
@@ -83,6 +96,7 @@ impl ConfigManager {
 	fn update(
 		context: EventContext<notify::Event>,
 		query: Query<&Self>,
+		mut aliases: ResMut<Aliases>,
 		mut keys: ResMut<Keys>,
 		mut theme: ResMut<Theme>,
 		mut settings: ResMut<Settings>,
@@ -97,6 +111,7 @@ impl ConfigManager {
 			| notify::EventKind::Modify(_)
 			| notify::EventKind::Remove(_) => {
 				let config = comp.parse_config()?;
+				*aliases = config.aliases;
 				*keys = config.keys;
 				*theme = config.theme;
 				*settings = config.settings;
@@ -124,7 +139,27 @@ impl ConfigManager {
 			);
 		}
 
-		Ok(builder.build()?.try_deserialize::<AppConfig>()?)
+		let config = builder.build()?.try_deserialize::<AppConfig>()?;
+		config.aliases.validate()?;
+		Self::validate_key_commands(&config)?;
+		Ok(config)
+	}
+
+	fn validate_key_commands(config: &AppConfig) -> Result<(), ConfigManagerError> {
+		for action in config.keys.actions() {
+			let InputAction::Command(input) = action else {
+				continue;
+			};
+			for command in config.aliases.expand(input)? {
+				if let Err(error) = command.parse::<Command>() {
+					return Err(ConfigManagerError::InvalidKeyCommand {
+						command,
+						error: Box::new(error),
+					});
+				}
+			}
+		}
+		Ok(())
 	}
 }
 
@@ -137,4 +172,74 @@ mod tests {
 		let config_manager = ConfigManager::new(None);
 		assert!(config_manager.parse_config().is_ok());
 	}
+
+	#[test]
+	fn alias_cycles_are_config_errors() {
+		let path = std::env::temp_dir().join(format!("sonas-aliases-{}.toml", std::process::id()));
+		std::fs::write(
+			&path,
+			"[aliases]\nnewest = \"album list sort=desc\"\na = \"b\"\nb = \"a\"\n",
+		)
+		.unwrap();
+
+		let config_manager = ConfigManager::new(Some(path.clone()));
+		let result = config_manager.parse_config();
+		std::fs::remove_file(&path).unwrap();
+
+		assert!(matches!(
+			result,
+			Err(ConfigManagerError::InvalidAliases(AliasError::Cycle(_)))
+		));
+	}
+
+	#[test]
+	fn keys_bind_commands() {
+		let path = std::env::temp_dir().join(format!("sonas-keys-{}.toml", std::process::id()));
+		std::fs::write(
+			&path,
+			"[aliases]\nnewest = \"album list sort=desc\"\n\n[keys]\nnewest = \"n\"\n",
+		)
+		.unwrap();
+
+		let config_manager = ConfigManager::new(Some(path.clone()));
+		let result = config_manager.parse_config();
+		std::fs::remove_file(&path).unwrap();
+
+		let config = result.unwrap();
+		let keys = serde_json::to_value(&*config.keys).unwrap();
+		assert_eq!(keys["newest"], "n");
+		assert_eq!(keys["quit"], "q");
+		assert_eq!(
+			config.aliases.expand("newest"),
+			Ok(vec!["album list sort=desc".to_string()])
+		);
+	}
+
+	#[test]
+	fn invalid_key_commands_are_config_errors() {
+		let path = std::env::temp_dir().join(format!("sonas-bad-keys-{}.toml", std::process::id()));
+		let parse = |keys: &str| {
+			let aliases = "tracks = \"album list-tracks $1\"";
+			std::fs::write(&path, format!("[aliases]\n{aliases}\n\n[keys]\n{keys}\n")).unwrap();
+			ConfigManager::new(Some(path.clone())).parse_config()
+		};
+
+		for (keys, command) in [
+			("\"player tggle\" = \"t\"", "player tggle"),
+			("quitt = \"Q\"", "quitt"),
+		] {
+			assert!(matches!(
+				parse(keys),
+				Err(ConfigManagerError::InvalidKeyCommand { command: found, .. }) if found == command
+			));
+		}
+		assert!(matches!(
+			parse("tracks = \"t\""),
+			Err(ConfigManagerError::InvalidAliases(
+				AliasError::MissingParameter { .. }
+			))
+		));
+		assert!(parse("\"tracks 3\" = \"t\"").is_ok());
+		std::fs::remove_file(&path).unwrap();
+	}
 }
@@ -3,7 +3,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{AppEvent, util::QuadDirection};
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum InputAction {
 	Quit,
@@ -19,13 +19,15 @@ pub enum InputAction {
 	ScrollFullPageDown,
 	ScrollFullPageUp,
 	TestError,
+	#[serde(untagged)]
+	Command(String),
 }
 
 impl Action for InputAction {
 	type AppEvent = AppEvent;
 
 	fn app_event(&self) -> Self::AppEvent {
-		match *self {
+		match self {
 			InputAction::Quit => AppEvent::Quit,
 			InputAction::Suspend => AppEvent::Suspend,
 			InputAction::CursorUp => AppEvent::MoveCursor(QuadDirection::Up),
@@ -57,6 +59,7 @@ impl Action for InputAction {
 				fraction: 1.,
 			},
 			InputAction::TestError => AppEvent::TestError("test error please ignore".to_owned()),
+			InputAction::Command(command) => AppEvent::RunCommand(command.clone()),
 		}
 	}
 }
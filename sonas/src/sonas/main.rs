@@ -19,12 +19,14 @@ use util::OctDirection;
 async fn main() -> eyre::Result<()> {
 	color_eyre::install()?;
 	let cli = Cli::new();
+	let instance = cli.instance()?;
 	App::new()
 		.with_tick_interval(Duration::from_secs_f64(0.25))
 		.with_frame_interval(Duration::from_secs_f64(1. / 144.))
 		.with_entity(|e| {
 			e.with_component(ErrorReporterComponent::new())?
 				.with_component(ConfigManager::new(cli.config_path()))?
+				.with_component(DaemonComponent::new(instance, !cli.args.no_spawn))?
 				.with_component(RootComponent::default())
 		})?
 		.with_entity(|e| e.with_component(FpsComponent::new(OctDirection::UpRight)))?
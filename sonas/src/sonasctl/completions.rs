@@ -0,0 +1,502 @@
+use std::fmt::Write as _;
+use std::str::FromStr;
+
+use sonas::protocol::PlainVerb;
+use sonas_parser::schema::{ArgumentKind, ArgumentSchema, CommandsSchema, SubcommandSchema};
+use thiserror::Error;
+
+const BIN: &str = "sonasctl";
+const BUILTINS: &[(&str, &str)] = &[
+	(PlainVerb::Help.name(), "Show help for a command"),
+	("completions", "Generate a shell completion script"),
+	("schema", "Print the command schema as JSON"),
+	(
+		PlainVerb::Subscribe.name(),
+		"Print events pushed by sonasd for the given topics",
+	),
+	("daemon", "Manage the sonasd process"),
+];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Shell {
+	Bash,
+	Zsh,
+	Fish,
+}
+
+impl Shell {
+	pub const NAMES: &[&str] = &["bash", "zsh", "fish"];
+
+	pub fn generate(self, commands: CommandsSchema) -> String {
+		let grammar = Grammar::new(commands);
+		match self {
+			Shell::Bash => bash(&grammar),
+			Shell::Zsh => zsh(&grammar),
+			Shell::Fish => fish(&grammar),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Error)]
+#[error("unknown shell '{0}', expected one of bash, zsh, fish")]
+pub struct UnknownShellError(pub String);
+
+impl FromStr for Shell {
+	type Err = UnknownShellError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"bash" => Ok(Self::Bash),
+			"zsh" => Ok(Self::Zsh),
+			"fish" => Ok(Self::Fish),
+			other => Err(UnknownShellError(other.to_string())),
+		}
+	}
+}
+
+struct Grammar {
+	nodes: Vec<(String, Vec<(&'static str, &'static str)>)>,
+	leaves: Vec<(String, &'static SubcommandSchema)>,
+}
+
+impl Grammar {
+	fn new(commands: CommandsSchema) -> Self {
+		let mut grammar = Self {
+			nodes: Vec::new(),
+			leaves: Vec::new(),
+		};
+		grammar.add("", commands);
+		grammar
+	}
+
+	fn add(&mut self, path: &str, commands: CommandsSchema) {
+		let join = |name: &str| {
+			if path.is_empty() {
+				name.to_string()
+			} else {
+				format!("{path} {name}")
+			}
+		};
+
+		match commands {
+			CommandsSchema::Categories(categories) => {
+				self.nodes.push((
+					path.to_string(),
+					categories.iter().map(|c| (c.name, c.doc)).collect(),
+				));
+				for category in categories {
+					self.add(&join(category.name), category.commands);
+				}
+			}
+			CommandsSchema::Subcommands(subcommands) => {
+				self.nodes.push((
+					path.to_string(),
+					subcommands.iter().map(|s| (s.name, s.doc)).collect(),
+				));
+				for subcommand in subcommands {
+					self.leaves.push((join(subcommand.name), subcommand));
+				}
+			}
+		}
+	}
+}
+
+fn words<'a>(names: impl IntoIterator<Item = &'a str>) -> String {
+	names.into_iter().collect::<Vec<_>>().join(" ")
+}
+
+fn positional_arguments(subcommand: &SubcommandSchema) -> impl Iterator<Item = &ArgumentSchema> {
+	subcommand
+		.arguments
+		.iter()
+		.filter(|arg| arg.kind.is_positional())
+}
+
+fn escape_single_quoted(text: &str) -> String {
+	text.replace('\'', r"'\''")
+}
+
+fn bash(grammar: &Grammar) -> String {
+	let mut script = String::new();
+	let _ = writeln!(script, "_{BIN}_children() {{\n\tcase \"$1\" in");
+	for (path, children) in &grammar.nodes {
+		let _ = writeln!(
+			script,
+			"\t\t\"{path}\") echo \"{}\" ;;",
+			words(children.iter().map(|(name, _)| *name))
+		);
+	}
+	script.push_str("\t\t*) return 1 ;;\n\tesac\n}\n\n");
+
+	let _ = writeln!(script, "_{BIN}() {{");
+	script.push_str(
+		"\tlocal line=\"${COMP_LINE:0:COMP_POINT}\"\n\
+		\tlocal -a words\n\
+		\tread -ra words <<< \"$line\"\n\
+		\t[[ \"$line\" =~ [[:space:]]$ || ${#words[@]} -eq 0 ]] && words+=(\"\")\n\
+		\tlocal cword=$((${#words[@]} - 1))\n\
+		\tlocal cur=\"${words[cword]}\" key=\"\" candidates=\"\"\n\
+		\tlocal prefix=\"\" children help=0 i=1\n\
+		\n",
+	);
+
+	let _ = writeln!(
+		script,
+		"\tif ((cword > 1)) && [[ \"${{words[1]}}\" == completions ]]; then\n\
+		\t\t((cword == 2)) && candidates=\"{}\"\n\
+		\t\tCOMPREPLY=($(compgen -W \"$candidates\" -- \"$cur\"))\n\
+		\t\treturn\n\
+		\tfi\n\
+		\tif ((cword > 1)) && [[ \"${{words[1]}}\" == help ]]; then\n\
+		\t\thelp=1 i=2\n\
+		\tfi\n",
+		words(Shell::NAMES.iter().copied())
+	);
+	let _ = writeln!(
+		script,
+		"\twhile ((i < cword)) && children=\"$(_{BIN}_children \"$prefix\")\"; do\n\
+		\t\t[[ \" $children \" == *\" ${{words[i]}} \"* ]] || break\n\
+		\t\tprefix=\"${{prefix:+$prefix }}${{words[i]}}\"\n\
+		\t\t((i++))\n\
+		\tdone\n"
+	);
+	let _ = writeln!(
+		script,
+		"\tif ((i == cword)) && children=\"$(_{BIN}_children \"$prefix\")\"; then\n\
+		\t\tcandidates=\"$children\"\n\
+		\t\t[[ -z \"$prefix\" && $help -eq 0 ]] && candidates=\"{} $candidates\"\n\
+		\telif ((help == 0)); then",
+		words(BUILTINS.iter().map(|(name, _)| *name))
+	);
+	script.push_str(
+		"\t\tlocal positional=0 word\n\
+		\t\tfor word in \"${words[@]:i:cword-i}\"; do\n\
+		\t\t\t[[ \"$word\" != *=* ]] && ((positional++))\n\
+		\t\tdone\n\
+		\t\tcase \"$prefix\" in\n",
+	);
+	for (path, subcommand) in &grammar.leaves {
+		let _ = writeln!(script, "\t\t\t\"{path}\")\n\t\t\t\tcase \"$cur\" in");
+		for arg in subcommand.arguments.iter().filter(|a| !a.values.is_empty()) {
+			let _ = writeln!(
+				script,
+				"\t\t\t\t\t{name}=*) key={name} candidates=\"{values}\" ;;",
+				name = arg.name,
+				values = words(arg.values.iter().copied()),
+			);
+		}
+		let _ = writeln!(
+			script,
+			"\t\t\t\t\t*)\n\t\t\t\t\t\tcandidates=\"{}\"",
+			subcommand
+				.arguments
+				.iter()
+				.map(|a| format!("{}=", a.name))
+				.collect::<Vec<_>>()
+				.join(" ")
+		);
+		for (index, arg) in positional_arguments(subcommand).enumerate() {
+			if arg.values.is_empty() {
+				continue;
+			}
+			let condition = match arg.kind {
+				ArgumentKind::Variadic => format!("positional >= {index}"),
+				_ => format!("positional == {index}"),
+			};
+			let _ = writeln!(
+				script,
+				"\t\t\t\t\t\t(({condition})) && candidates+=\" {}\"",
+				words(arg.values.iter().copied())
+			);
+		}
+		script.push_str("\t\t\t\t\t\t;;\n\t\t\t\tesac\n\t\t\t\t;;\n");
+	}
+	script.push_str("\t\tesac\n\tfi\n\n");
+
+	script.push_str(
+		"\t[[ -n \"$key\" ]] && cur=\"${cur#*=}\"\n\
+		\tCOMPREPLY=($(compgen -W \"$candidates\" -- \"$cur\"))\n\
+		\tif [[ -n \"$key\" && \"$COMP_WORDBREAKS\" != *=* ]]; then\n\
+		\t\tCOMPREPLY=(\"${COMPREPLY[@]/#/$key=}\")\n\
+		\tfi\n\
+		\tif [[ ${#COMPREPLY[@]} -eq 1 && \"${COMPREPLY[0]}\" == *= ]]; then\n\
+		\t\tcompopt -o nospace\n\
+		\tfi\n\
+		}\n\n",
+	);
+	let _ = writeln!(script, "complete -F _{BIN} {BIN}");
+	script
+}
+
+fn zsh_describe(name: &str, doc: &str) -> String {
+	let doc = doc.lines().next().unwrap_or_default();
+	let entry = if doc.is_empty() {
+		name.replace(':', r"\:")
+	} else {
+		format!("{}:{doc}", name.replace(':', r"\:"))
+	};
+	format!("'{}'", escape_single_quoted(&entry))
+}
+
+fn zsh(grammar: &Grammar) -> String {
+	let mut script = String::new();
+	let _ = writeln!(
+		script,
+		"#compdef {BIN}\n\n_{BIN}_children() {{\n\tcase \"$1\" in"
+	);
+	for (path, children) in &grammar.nodes {
+		let _ = writeln!(
+			script,
+			"\t\t\"{path}\")\n\t\t\tnames=({})\n\t\t\treply=({})\n\t\t\t;;",
+			words(children.iter().map(|(name, _)| *name)),
+			children
+				.iter()
+				.map(|(name, doc)| zsh_describe(name, doc))
+				.collect::<Vec<_>>()
+				.join(" ")
+		);
+	}
+	script.push_str("\t\t*) return 1 ;;\n\tesac\n}\n\n");
+
+	let _ = writeln!(script, "_{BIN}() {{");
+	script.push_str(
+		"\tlocal -a candidates keys values names reply\n\
+		\tlocal cur=\"${words[CURRENT]}\" prefix=\"\" help=0 i=2\n\n",
+	);
+	let _ = writeln!(
+		script,
+		"\tif ((CURRENT > 2)) && [[ \"${{words[2]}}\" == completions ]]; then\n\
+		\t\t((CURRENT == 3)) && compadd -- {}\n\
+		\t\treturn\n\
+		\tfi\n\
+		\tif ((CURRENT > 2)) && [[ \"${{words[2]}}\" == help ]]; then\n\
+		\t\thelp=1 i=3\n\
+		\tfi",
+		words(Shell::NAMES.iter().copied())
+	);
+	let _ = writeln!(
+		script,
+		"\twhile ((i < CURRENT)) && _{BIN}_children \"$prefix\" && ((${{names[(Ie)${{words[i]}}]}})); do\n\
+		\t\tprefix=\"${{prefix:+$prefix }}${{words[i]}}\"\n\
+		\t\t((i++))\n\
+		\tdone\n"
+	);
+	let _ = writeln!(
+		script,
+		"\tif ((i == CURRENT)) && _{BIN}_children \"$prefix\"; then\n\
+		\t\tcandidates=(\"${{reply[@]}}\")\n\
+		\t\t[[ -z \"$prefix\" && $help -eq 0 ]] && candidates=({} \"${{candidates[@]}}\")\n\
+		\t\t_describe command candidates\n\
+		\t\treturn\n\
+		\tfi\n\
+		\t((help)) && return\n",
+		BUILTINS
+			.iter()
+			.map(|(name, doc)| zsh_describe(name, doc))
+			.collect::<Vec<_>>()
+			.join(" ")
+	);
+
+	script.push_str(
+		"\tlocal positional=0 word\n\
+		\tfor word in \"${(@)words[i,CURRENT-1]}\"; do\n\
+		\t\t[[ \"$word\" != *=* ]] && ((positional++))\n\
+		\tdone\n\n\
+		\tcase \"$prefix\" in\n",
+	);
+	for (path, subcommand) in &grammar.leaves {
+		let _ = writeln!(script, "\t\t\"{path}\")\n\t\t\tcase \"$cur\" in");
+		for arg in subcommand.arguments.iter().filter(|a| !a.values.is_empty()) {
+			let _ = writeln!(
+				script,
+				"\t\t\t\t{}=*)\n\t\t\t\t\tcompset -P '*='\n\t\t\t\t\tcompadd -- {}\n\t\t\t\t\treturn\n\t\t\t\t\t;;",
+				arg.name,
+				words(arg.values.iter().copied())
+			);
+		}
+		let _ = writeln!(
+			script,
+			"\t\t\t\t*)\n\t\t\t\t\tkeys=({})",
+			subcommand
+				.arguments
+				.iter()
+				.map(|a| zsh_describe(&format!("{}=", a.name), a.doc))
+				.collect::<Vec<_>>()
+				.join(" ")
+		);
+		for (index, arg) in positional_arguments(subcommand).enumerate() {
+			if arg.values.is_empty() {
+				continue;
+			}
+			let condition = match arg.kind {
+				ArgumentKind::Variadic => format!("positional >= {index}"),
+				_ => format!("positional == {index}"),
+			};
+			let _ = writeln!(
+				script,
+				"\t\t\t\t\t(({condition})) && values+=({})",
+				words(arg.values.iter().copied())
+			);
+		}
+		script.push_str("\t\t\t\t\t;;\n\t\t\tesac\n\t\t\t;;\n");
+	}
+	script.push_str(
+		"\tesac\n\n\
+		\t_describe -t arguments argument keys -S ''\n\
+		\t((${#values[@]})) && compadd -- \"${values[@]}\"\n\
+		}\n\n",
+	);
+	let _ = writeln!(script, "_{BIN} \"$@\"");
+	script
+}
+
+fn fish_entries<'a, S: AsRef<str>>(entries: impl IntoIterator<Item = (S, &'a str)>) -> String {
+	entries
+		.into_iter()
+		.map(|(name, doc)| {
+			let doc = doc.lines().next().unwrap_or_default();
+			format!("'{}'", escape_fish(&format!("{}\t{doc}", name.as_ref())))
+		})
+		.collect::<Vec<_>>()
+		.join(" ")
+}
+
+fn escape_fish(text: &str) -> String {
+	text.replace('\\', r"\\").replace('\'', r"\'")
+}
+
+fn fish(grammar: &Grammar) -> String {
+	let mut script = String::new();
+	let _ = writeln!(script, "function __{BIN}_children\n\tswitch \"$argv[1]\"");
+	for (path, children) in &grammar.nodes {
+		let _ = writeln!(
+			script,
+			"\t\tcase '{path}'\n\t\t\tprintf '%s\\n' {}",
+			fish_entries(children.iter().copied())
+		);
+	}
+	script.push_str("\t\tcase '*'\n\t\t\treturn 1\n\tend\nend\n\n");
+
+	let _ = writeln!(script, "function __{BIN}_complete");
+	script.push_str(
+		"\tset -l tokens (commandline -opc)\n\
+		\tset -e tokens[1]\n\
+		\tset -l cur (commandline -ct)\n\
+		\tset -l count (count $tokens)\n\n",
+	);
+	let _ = writeln!(
+		script,
+		"\tif test $count -eq 0\n\
+		\t\tprintf '%s\\n' {}\n\
+		\t\t__{BIN}_children ''\n\
+		\t\treturn\n\
+		\tend\n\
+		\tif test \"$tokens[1]\" = completions\n\
+		\t\ttest $count -eq 1; and printf '%s\\n' {}\n\
+		\t\treturn\n\
+		\tend\n",
+		fish_entries(BUILTINS.iter().copied()),
+		words(Shell::NAMES.iter().copied())
+	);
+	let _ = writeln!(
+		script,
+		"\tset -l help 0\n\
+		\tset -l i 1\n\
+		\tif test \"$tokens[1]\" = help\n\
+		\t\tset help 1\n\
+		\t\tset i 2\n\
+		\tend\n\
+		\tset -l prefix ''\n\
+		\twhile test $i -le $count\n\
+		\t\t__{BIN}_children \"$prefix\" >/dev/null; or break\n\
+		\t\tcontains -- $tokens[$i] (__{BIN}_children \"$prefix\" | string split -f1 \\t); or break\n\
+		\t\tset prefix (string trim -- \"$prefix $tokens[$i]\")\n\
+		\t\tset i (math $i + 1)\n\
+		\tend\n\n\
+		\tif test $i -gt $count\n\
+		\t\t__{BIN}_children \"$prefix\"; and return\n\
+		\tend\n\
+		\ttest $help -eq 1; and return\n"
+	);
+
+	script.push_str(
+		"\tset -l positional 0\n\
+		\tif test $i -le $count\n\
+		\t\tfor token in $tokens[$i..-1]\n\
+		\t\t\tstring match -q -- '*=*' $token; or set positional (math $positional + 1)\n\
+		\t\tend\n\
+		\tend\n\n\
+		\tswitch \"$prefix\"\n",
+	);
+	for (path, subcommand) in &grammar.leaves {
+		let _ = writeln!(script, "\t\tcase '{path}'\n\t\t\tswitch $cur");
+		for arg in subcommand.arguments.iter().filter(|a| !a.values.is_empty()) {
+			let _ = writeln!(
+				script,
+				"\t\t\t\tcase '{name}=*'\n\t\t\t\t\tprintf '{name}=%s\\n' {values}",
+				name = arg.name,
+				values = words(arg.values.iter().copied())
+			);
+		}
+		let _ = writeln!(
+			script,
+			"\t\t\t\tcase '*'\n\t\t\t\t\tprintf '%s\\n' {}",
+			fish_entries(
+				subcommand
+					.arguments
+					.iter()
+					.map(|a| (format!("{}=", a.name), a.doc))
+			)
+		);
+		for (index, arg) in positional_arguments(subcommand).enumerate() {
+			if arg.values.is_empty() {
+				continue;
+			}
+			let condition = match arg.kind {
+				ArgumentKind::Variadic => format!("-ge {index}"),
+				_ => format!("-eq {index}"),
+			};
+			let _ = writeln!(
+				script,
+				"\t\t\t\t\ttest $positional {condition}; and printf '%s\\n' {}",
+				words(arg.values.iter().copied())
+			);
+		}
+		script.push_str("\t\t\tend\n");
+	}
+	script.push_str("\tend\nend\n\n");
+	let _ = writeln!(script, "complete -c {BIN} -f -a '(__{BIN}_complete)'");
+	script
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use sonas::Command;
+	use sonas_parser::schema::CommandSchema;
+
+	#[test]
+	fn scripts_contain_grammar() {
+		for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+			let script = shell.generate(Command::SCHEMA);
+			for word in ["album", "list-tracks", "sort=", "ascending", "descending"] {
+				assert!(
+					script.contains(word),
+					"{shell:?} completion script is missing '{word}'"
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn parses_shell_names() {
+		for name in Shell::NAMES {
+			assert!(name.parse::<Shell>().is_ok());
+		}
+		assert_eq!(
+			"tcsh".parse::<Shell>(),
+			Err(UnknownShellError("tcsh".to_string()))
+		);
+	}
+}
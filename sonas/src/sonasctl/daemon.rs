@@ -0,0 +1,116 @@
+use std::{io, thread, time::Duration};
+
+use clap::Subcommand;
+use serde_json::json;
+use sonas::{
+	instance::{DaemonStatus, Instance},
+	protocol::RequestBody,
+	server::Client,
+};
+
+use super::{Args, Exit, output::OutputFormat};
+
+#[derive(Subcommand, Debug, Clone, Copy)]
+pub enum DaemonCommand {
+	/// Start sonasd in the background unless it is already running
+	Start,
+	/// Ask sonasd to shut down and wait until it has exited
+	Stop,
+	/// Show whether sonasd is running and its pid
+	Status,
+	/// Stop sonasd if it is running and start it again
+	Restart,
+}
+
+pub fn run(args: &Args, command: DaemonCommand) -> io::Result<Exit> {
+	let instance = super::instance(args)?;
+	let (format, quiet) = (args.format, args.quiet);
+	let say = move |message: String| {
+		if !quiet && format != OutputFormat::Json {
+			println!("{message}");
+		}
+	};
+
+	super::with_timeout(args.timeout.0, move || match command {
+		DaemonCommand::Start => match instance.status()? {
+			DaemonStatus::Running { .. } => {
+				say(format!("sonasd is already running on {}", instance.socket));
+				Ok(Exit::Success)
+			}
+			DaemonStatus::Stopped => start(&instance, say),
+		},
+		DaemonCommand::Stop => match instance.status()? {
+			DaemonStatus::Running { .. } => {
+				stop(&instance)?;
+				say(format!("sonasd stopped on {}", instance.socket));
+				Ok(Exit::Success)
+			}
+			DaemonStatus::Stopped => {
+				say(format!("sonasd is not running on {}", instance.socket));
+				Ok(Exit::NotRunning)
+			}
+		},
+		DaemonCommand::Status => {
+			let status = instance.status()?;
+			if !quiet {
+				print_status(format, &instance, status);
+			}
+			Ok(match status {
+				DaemonStatus::Running { .. } => Exit::Success,
+				DaemonStatus::Stopped => Exit::NotRunning,
+			})
+		}
+		DaemonCommand::Restart => {
+			if let DaemonStatus::Running { .. } = instance.status()? {
+				stop(&instance)?;
+			}
+			start(&instance, say)
+		}
+	})
+}
+
+fn start(instance: &Instance, say: impl Fn(String)) -> io::Result<Exit> {
+	instance.spawn()?;
+	match instance.status()? {
+		DaemonStatus::Running { pid: Some(pid) } => {
+			say(format!("sonasd started on {} (pid {pid})", instance.socket));
+		}
+		_ => say(format!("sonasd started on {}", instance.socket)),
+	}
+	Ok(Exit::Success)
+}
+
+/// Sends the shutdown request and waits for the pid file to be unlocked
+fn stop(instance: &Instance) -> io::Result<()> {
+	Client::connect_to(instance.name()?)?.request(RequestBody::Shutdown)?;
+	while instance.status()? != DaemonStatus::Stopped {
+		thread::sleep(Duration::from_millis(50));
+	}
+	Ok(())
+}
+
+fn print_status(format: OutputFormat, instance: &Instance, status: DaemonStatus) {
+	let pid = match status {
+		DaemonStatus::Running { pid } => pid,
+		DaemonStatus::Stopped => None,
+	};
+	let running = status != DaemonStatus::Stopped;
+
+	match format {
+		OutputFormat::Plain => match (running, pid) {
+			(true, Some(pid)) => println!("sonasd is running on {} (pid {pid})", instance.socket),
+			(true, None) => println!("sonasd is running on {}", instance.socket),
+			(false, _) => println!("sonasd is not running on {}", instance.socket),
+		},
+		OutputFormat::Tsv => println!(
+			"{}\t{}\t{}",
+			if running { "running" } else { "stopped" },
+			pid.map_or("-".to_string(), |pid| pid.to_string()),
+			instance.socket
+		),
+		OutputFormat::Json => println!(
+			"{}",
+			json!({ "socket": instance.socket, "running": running, "pid": pid })
+		),
+	}
+}
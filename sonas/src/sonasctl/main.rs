@@ -1,13 +1,447 @@
-use sonas::server;
-use std::env;
+mod completions;
+mod daemon;
+mod output;
 
-fn main() {
-	let args = env::args().skip(1).collect::<Vec<_>>().join(" ");
-	println!("{:?}", args);
-	let result = server::send_line(&args);
+use clap::{CommandFactory, Parser, Subcommand};
+use completions::Shell;
+use daemon::DaemonCommand;
+use output::OutputFormat;
+use sonas::{
+	Command,
+	alias::AliasTable,
+	event::Topic,
+	instance::{self, Instance},
+	paths,
+	protocol::{ErrorKind, RequestBody, Response, Status},
+	script::OnError,
+	server::Client,
+};
+use sonas_parser::{Timestamp, schema::CommandSchema, tokenizer};
+use std::{
+	fs,
+	io::{self, Write as _},
+	path::PathBuf,
+	process::ExitCode,
+	sync::mpsc,
+	thread,
+	time::Duration,
+};
+use thiserror::Error;
 
-	match result {
-		Ok(x) => println!("{:?}", x),
-		Err(e) => println!("{:?}", e),
+const EXIT_CODES: &str = "\
+Exit codes:
+  0  Success
+  1  A command failed
+  2  A command or the arguments could not be parsed
+  3  sonasd is not running and could not be started
+  4  sonasd did not respond in time";
+
+/// Sends commands to a running sonasd
+#[derive(Parser, Debug)]
+#[command(
+	name = "sonasctl",
+	version,
+	disable_help_subcommand = true,
+	arg_required_else_help = true,
+	after_help = EXIT_CODES,
+)]
+struct Args {
+	/// How results are printed
+	#[arg(long, value_enum, default_value_t)]
+	format: OutputFormat,
+
+	/// Socket of sonasd, either a namespaced name or a file path [env: SONAS_SOCKET]
+	#[arg(long, value_name = "PATH")]
+	socket: Option<String>,
+
+	/// Name of the sonasd instance to talk to [env: SONAS_INSTANCE]
+	#[arg(
+		long,
+		value_name = "NAME",
+		value_parser = instance::parse_name,
+		conflicts_with = "socket"
+	)]
+	instance: Option<String>,
+
+	/// How long to wait for sonasd to respond, like 5s or 1m30s
+	#[arg(long, value_name = "DURATION", default_value = "10s")]
+	timeout: Timestamp,
+
+	/// Print nothing on success, errors are still reported on stderr
+	#[arg(short, long)]
+	quiet: bool,
+
+	/// Fail instead of starting sonasd when it is not running
+	#[arg(long)]
+	no_spawn: bool,
+
+	/// Run the commands in a script file, or - for stdin
+	#[arg(short, long, value_name = "FILE")]
+	file: Option<PathBuf>,
+
+	/// Keep running the script after a command fails
+	#[arg(long = "continue", requires = "file")]
+	keep_going: bool,
+
+	#[command(subcommand)]
+	action: Option<Action>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Action {
+	/// Generate a shell completion script
+	Completions { shell: Shell },
+	/// Print the command schema as JSON
+	Schema,
+	/// Print events pushed by sonasd for the given topics
+	Subscribe { topics: Vec<Topic> },
+	/// Manage the sonasd process
+	Daemon {
+		#[command(subcommand)]
+		command: DaemonCommand,
+	},
+	/// Any command understood by sonasd, or `help [command]`
+	#[command(external_subcommand)]
+	Command(Vec<String>),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Exit {
+	Success = 0,
+	Failure = 1,
+	Usage = 2,
+	NotRunning = 3,
+	Timeout = 4,
+}
+
+/// sonasd was not running and starting it failed
+#[derive(Debug, Error)]
+#[error(transparent)]
+struct SpawnError(io::Error);
+
+impl From<SpawnError> for io::Error {
+	fn from(error: SpawnError) -> Self {
+		io::Error::other(error)
+	}
+}
+
+impl From<Exit> for ExitCode {
+	fn from(exit: Exit) -> Self {
+		ExitCode::from(exit as u8)
+	}
+}
+
+impl From<&io::Error> for Exit {
+	fn from(error: &io::Error) -> Self {
+		match error.kind() {
+			_ if instance::is_not_running(error) => Exit::NotRunning,
+			_ if error
+				.get_ref()
+				.is_some_and(|error| error.is::<SpawnError>()) =>
+			{
+				Exit::NotRunning
+			}
+			io::ErrorKind::TimedOut => Exit::Timeout,
+			_ => Exit::Failure,
+		}
+	}
+}
+
+impl From<&Response> for Exit {
+	fn from(response: &Response) -> Self {
+		match &response.status {
+			Status::Ok => Exit::Success,
+			Status::Error { errors }
+				if errors
+					.iter()
+					.all(|error| matches!(error.kind, ErrorKind::Parse | ErrorKind::Alias)) =>
+			{
+				Exit::Usage
+			}
+			Status::Error { .. } => Exit::Failure,
+		}
+	}
+}
+
+fn main() -> ExitCode {
+	let args = Args::parse();
+	if args.file.is_some() && args.action.is_some() {
+		Args::command()
+			.error(
+				clap::error::ErrorKind::ArgumentConflict,
+				"--file cannot be used with a command",
+			)
+			.exit();
+	}
+
+	let body = match &args.action {
+		Some(Action::Completions { shell }) => {
+			print!("{}", shell.generate(Command::SCHEMA));
+			return Exit::Success.into();
+		}
+		Some(Action::Schema) => {
+			return match serde_json::to_string_pretty(&Command::SCHEMA) {
+				Ok(json) => {
+					println!("{json}");
+					Exit::Success.into()
+				}
+				Err(e) => {
+					eprintln!("error: {e}");
+					Exit::Failure.into()
+				}
+			};
+		}
+		Some(Action::Subscribe { topics }) => {
+			return match subscribe(&args, topics.clone()) {
+				Ok(exit) => exit.into(),
+				Err(e) => report(&e).into(),
+			};
+		}
+		Some(Action::Daemon { command }) => {
+			return match daemon::run(&args, *command) {
+				Ok(exit) => exit.into(),
+				Err(e) => report(&e).into(),
+			};
+		}
+		Some(Action::Command(words)) => command_body(command_line(words)),
+		None => script_body(&args),
+	};
+	let body = match body {
+		Ok(body) => body,
+		Err((exit, message)) => {
+			eprintln!("error: {message}");
+			return exit.into();
+		}
+	};
+
+	let response = match request(&args, body) {
+		Ok(response) => response,
+		Err(e) => return report(&e).into(),
+	};
+	let (mut stdout, mut stderr) = (io::stdout().lock(), io::stderr().lock());
+	let written = match args.quiet {
+		true => OutputFormat::Tsv.write_response(&mut io::sink(), &mut stderr, &response),
+		false => args
+			.format
+			.write_response(&mut stdout, &mut stderr, &response),
+	};
+	match written {
+		Ok(()) => Exit::from(&response).into(),
+		Err(e) => report(&e).into(),
+	}
+}
+
+fn command_body(command: String) -> Result<RequestBody, (Exit, String)> {
+	let aliases = paths::config_path()
+		.and_then(|path| AliasTable::load(&path).ok())
+		.unwrap_or_default();
+
+	match aliases.expand(&command) {
+		Ok(commands) if commands != [command.as_str()] => Ok(RequestBody::Script {
+			script: commands.join("\n"),
+			on_error: Some(OnError::Stop),
+		}),
+		Ok(_) => Ok(match tokenizer::split_word(&command) {
+			("help", _, end) => RequestBody::Help {
+				path: command[end..].trim().to_string(),
+			},
+			_ => RequestBody::Command { command },
+		}),
+		Err(e) => Err((Exit::Usage, e.to_string())),
+	}
+}
+
+/// Quotes every word again, since the shell has already removed the quotes
+/// that kept it together. Only the value of a `key=value` word is quoted, and
+/// commas in it still separate list items
+fn command_line(words: &[String]) -> String {
+	let is_key = |key: &str| {
+		!key.is_empty()
+			&& key
+				.chars()
+				.all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+	};
+
+	words
+		.iter()
+		.map(|word| match word.split_once('=') {
+			Some((key, value)) if is_key(key) => {
+				let items = value.split(',').map(tokenizer::quote).collect::<Vec<_>>();
+				format!("{key}={}", items.join(","))
+			}
+			_ => tokenizer::quote(word).into_owned(),
+		})
+		.collect::<Vec<_>>()
+		.join(" ")
+}
+
+fn script_body(args: &Args) -> Result<RequestBody, (Exit, String)> {
+	let Some(path) = &args.file else {
+		return Err((Exit::Usage, "no command given".to_string()));
+	};
+
+	let script = match path.to_str() {
+		Some("-") => io::read_to_string(io::stdin()),
+		_ => fs::read_to_string(path),
+	}
+	.map_err(|e| {
+		(
+			Exit::Failure,
+			format!("failed to read {}: {e}", path.display()),
+		)
+	})?;
+
+	Ok(RequestBody::Script {
+		script,
+		on_error: Some(match args.keep_going {
+			true => OnError::Continue,
+			false => OnError::Stop,
+		}),
+	})
+}
+
+fn instance(args: &Args) -> io::Result<Instance> {
+	Ok(Instance::resolve(
+		args.socket.clone(),
+		args.instance.clone(),
+	)?)
+}
+
+/// Starts sonasd when nothing is listening, unless told not to
+fn connect(instance: &Instance, spawn: bool) -> io::Result<Client> {
+	match Client::connect_to(instance.name()?) {
+		Err(e) if spawn && instance::is_not_running(&e) => {
+			instance.spawn().map_err(SpawnError)?;
+			Client::connect_to(instance.name()?)
+		}
+		client => client,
+	}
+}
+
+fn request(args: &Args, body: RequestBody) -> io::Result<Response> {
+	let (instance, spawn) = (instance(args)?, !args.no_spawn);
+	with_timeout(args.timeout.0, move || {
+		connect(&instance, spawn)?.request(body)
+	})
+}
+
+fn subscribe(args: &Args, topics: Vec<Topic>) -> io::Result<Exit> {
+	let (instance, spawn) = (instance(args)?, !args.no_spawn);
+	let (mut client, response) = with_timeout(args.timeout.0, move || {
+		let mut client = connect(&instance, spawn)?;
+		let response = client.request(RequestBody::Subscribe { topics })?;
+		Ok((client, response))
+	})?;
+	if !response.is_ok() {
+		eprint!("{response}");
+		return Ok(Exit::from(&response));
+	}
+
+	let mut stdout = io::stdout().lock();
+	loop {
+		let push = client.next_push()?;
+		if !args.quiet {
+			args.format.write_push(&mut stdout, &push)?;
+			stdout.flush()?;
+		}
+	}
+}
+
+/// The local socket has no timeouts of its own, so the request runs on a
+/// separate thread that is abandoned once the time is up
+fn with_timeout<T: Send + 'static>(
+	timeout: Duration,
+	f: impl FnOnce() -> io::Result<T> + Send + 'static,
+) -> io::Result<T> {
+	let (sender, receiver) = mpsc::channel();
+	thread::spawn(move || sender.send(f()));
+
+	receiver.recv_timeout(timeout).unwrap_or_else(|_| {
+		Err(io::Error::new(
+			io::ErrorKind::TimedOut,
+			"timed out waiting for sonasd",
+		))
+	})
+}
+
+fn report(error: &io::Error) -> Exit {
+	let exit = Exit::from(error);
+	match exit {
+		Exit::NotRunning => eprintln!("error: sonasd is not running ({error})"),
+		_ => eprintln!("error: {error}"),
+	}
+	exit
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn arguments_are_valid() {
+		Args::command().debug_assert();
+	}
+
+	#[test]
+	fn failing_to_start_sonasd_means_it_is_not_running() {
+		let error = io::Error::other("failed to start sonasd: no config");
+		assert_eq!(Exit::from(&error), Exit::Failure);
+		assert_eq!(
+			Exit::from(&io::Error::from(SpawnError(error))),
+			Exit::NotRunning
+		);
+	}
+
+	#[test]
+	fn commands_are_passed_through() {
+		let args = Args::try_parse_from(["sonasctl", "--format", "tsv", "album", "list"]).unwrap();
+
+		assert_eq!(args.format, OutputFormat::Tsv);
+		assert!(matches!(
+			args.action,
+			Some(Action::Command(words)) if words == ["album", "list"]
+		));
+		assert!(Args::try_parse_from(["sonasctl", "--continue", "album", "list"]).is_err());
+	}
+
+	#[test]
+	fn words_keep_their_shell_quoting() {
+		let words = |words: &[&str]| {
+			words
+				.iter()
+				.map(|word| word.to_string())
+				.collect::<Vec<_>>()
+		};
+
+		assert_eq!(
+			command_line(&words(&["album", "search", "artist=Miles Davis"])),
+			r#"album search artist="Miles Davis""#
+		);
+		assert_eq!(
+			command_line(&words(&["set", "a b", "ids=3,7", "x y=z", "say=\"hi\""])),
+			r#"set "a b" ids=3,7 "x y=z" say="\"hi\"""#
+		);
+
+		let command = command_line(&words(&[
+			"album",
+			"search",
+			"artist=Miles Davis",
+			"exact=true",
+		]));
+		assert_eq!(
+			command.parse::<Command>(),
+			Ok(Command::Album(sonas::AlbumCommand::Search {
+				any: None,
+				artist: Some("Miles Davis".to_string()),
+				album: None,
+				title: None,
+				year: None,
+				exact: true,
+			}))
+		);
+		assert!(matches!(
+			command_body(command_line(&words(&["help", "album", "list"]))),
+			Ok(RequestBody::Help { path }) if path == "album list"
+		));
 	}
 }
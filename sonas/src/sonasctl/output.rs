@@ -0,0 +1,233 @@
+use std::io::{self, Write};
+
+use clap::ValueEnum;
+use sonas::{
+	event::Push,
+	execute::Reply,
+	protocol::{Response, Status},
+};
+use sonas_parser::Timestamp;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Default, ValueEnum)]
+pub enum OutputFormat {
+	/// Aligned tables with a header row
+	#[default]
+	Plain,
+	/// The responses of sonasd, one JSON object per line
+	Json,
+	/// One record per line with tab separated fields and no header
+	Tsv,
+}
+
+impl OutputFormat {
+	/// Writes the results to `out` and the errors to `err`, except in JSON
+	/// where the errors are part of the response
+	pub fn write_response(
+		self,
+		out: &mut impl Write,
+		err: &mut impl Write,
+		response: &Response,
+	) -> io::Result<()> {
+		if self == Self::Json {
+			return writeln!(out, "{}", serde_json::to_string(response)?);
+		}
+
+		for result in &response.results {
+			match self {
+				Self::Plain => write_table(out, result)?,
+				_ => write!(out, "{result}")?,
+			}
+		}
+		if let Status::Error { errors } = &response.status {
+			for error in errors {
+				write!(err, "{error}")?;
+			}
+		}
+		Ok(())
+	}
+
+	pub fn write_push(self, out: &mut impl Write, push: &Push) -> io::Result<()> {
+		match self {
+			Self::Json => writeln!(out, "{}", serde_json::to_string(push)?),
+			_ => write!(out, "{push}"),
+		}
+	}
+}
+
+fn write_table(out: &mut impl Write, reply: &Reply) -> io::Result<()> {
+	let (header, rows): (&[&str], Vec<Vec<String>>) = match reply {
+		Reply::Albums(albums) => (
+			&["ID", "ARTIST", "TITLE", "YEAR", "TRACKS"],
+			albums
+				.iter()
+				.map(|album| {
+					vec![
+						album.id.to_string(),
+						album.artist.clone(),
+						album.title.clone(),
+						album.year.map_or("-".to_string(), |year| year.to_string()),
+						album.track_count.to_string(),
+					]
+				})
+				.collect(),
+		),
+		Reply::Tracks(tracks) => (
+			&["#", "TITLE", "LENGTH"],
+			tracks
+				.iter()
+				.map(|track| {
+					vec![
+						track.number.to_string(),
+						track.title.clone(),
+						Timestamp(track.duration).to_string(),
+					]
+				})
+				.collect(),
+		),
+		Reply::Songs(songs) => (
+			&[
+				"POS", "ID", "ALBUM ID", "#", "ARTIST", "ALBUM", "TITLE", "LENGTH",
+			],
+			songs
+				.iter()
+				.map(|song| {
+					vec![
+						song.position
+							.map_or("-".to_string(), |position| position.to_string()),
+						song.id.map_or("-".to_string(), |id| id.to_string()),
+						song.album.to_string(),
+						song.track.number.to_string(),
+						song.artist.clone(),
+						song.album_title.clone(),
+						song.track.title.clone(),
+						Timestamp(song.track.duration).to_string(),
+					]
+				})
+				.collect(),
+		),
+		Reply::Ack | Reply::Help(_) | Reply::Status(_) => return write!(out, "{reply}"),
+	};
+
+	let header = header.iter().map(|cell| cell.to_string()).collect();
+	let rows = [header].into_iter().chain(rows).collect::<Vec<Vec<_>>>();
+	let widths = (0..rows[0].len())
+		.map(|column| {
+			rows.iter()
+				.map(|row| row[column].chars().count())
+				.max()
+				.unwrap_or_default()
+		})
+		.collect::<Vec<_>>();
+
+	for row in rows {
+		let line = row
+			.iter()
+			.zip(&widths)
+			.map(|(cell, &width)| format!("{cell:<width$}"))
+			.collect::<Vec<_>>()
+			.join("  ");
+		writeln!(out, "{}", line.trim_end())?;
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sonas::{
+		execute::{AlbumSummary, Song},
+		library::Track,
+	};
+	use std::time::Duration;
+
+	fn render(format: OutputFormat, response: &Response) -> (String, String) {
+		let (mut out, mut err) = (Vec::new(), Vec::new());
+		format.write_response(&mut out, &mut err, response).unwrap();
+		(
+			String::from_utf8(out).unwrap(),
+			String::from_utf8(err).unwrap(),
+		)
+	}
+
+	#[test]
+	fn tables_are_aligned() {
+		let album = |id, title: &str, year| AlbumSummary {
+			id,
+			title: title.to_string(),
+			artist: "Artist".to_string(),
+			year,
+			track_count: 12,
+		};
+		let reply = Reply::Albums(vec![
+			album(1, "A Long Title", Some(2001)),
+			album(10, "Short", None),
+		]);
+		let response = Response::new(1, vec![reply], Vec::new());
+
+		assert_eq!(
+			render(OutputFormat::Plain, &response).0,
+			"ID  ARTIST  TITLE         YEAR  TRACKS\n\
+			1   Artist  A Long Title  2001  12\n\
+			10  Artist  Short         -     12\n"
+		);
+		assert_eq!(
+			render(OutputFormat::Tsv, &response).0,
+			"1\tArtist\tA Long Title\t2001\t12\n10\tArtist\tShort\t-\t12\n"
+		);
+	}
+
+	#[test]
+	fn tracks_show_lengths() {
+		let reply = Reply::Tracks(vec![Track {
+			number: 1,
+			title: "Intro".to_string(),
+			duration: Duration::from_secs(62),
+		}]);
+		let response = Response::new(1, vec![reply], Vec::new());
+
+		assert_eq!(
+			render(OutputFormat::Plain, &response).0,
+			"#  TITLE  LENGTH\n1  Intro  1:02\n"
+		);
+	}
+
+	#[test]
+	fn songs_show_their_queue_entry() {
+		let song = |position, id| Song {
+			position,
+			id,
+			album: 4,
+			album_title: "Blue".to_string(),
+			artist: "Joni".to_string(),
+			year: None,
+			track: Track {
+				number: 2,
+				title: "River".to_string(),
+				duration: Duration::from_secs(240),
+			},
+		};
+		let reply = Reply::Songs(vec![song(Some(0), Some(17)), song(None, None)]);
+		let response = Response::new(1, vec![reply], Vec::new());
+
+		assert_eq!(
+			render(OutputFormat::Tsv, &response).0,
+			"0\t17\t4\t2\tJoni\tBlue\tRiver\t4:00\n-\t-\t4\t2\tJoni\tBlue\tRiver\t4:00\n"
+		);
+	}
+
+	#[test]
+	fn errors_go_to_stderr_except_in_json() {
+		let error =
+			sonas::protocol::ErrorPayload::from(&sonas::execute::ExecuteError::UnknownAlbum(3));
+		let response = Response::error(2, error);
+
+		assert_eq!(
+			render(OutputFormat::Plain, &response),
+			(String::new(), "error: no album with id 3\n".to_string())
+		);
+
+		let (out, err) = render(OutputFormat::Json, &response);
+		assert!(out.starts_with(r#"{"id":2,"status":"error","errors":[{"kind":"execute""#));
+		assert!(err.is_empty());
+	}
+}
@@ -0,0 +1,407 @@
+//! The HTTP API: every command as a JSON endpoint, player events as
+//! Server-Sent Events and a small web remote. Every request has to carry the
+//! configured token, either as a bearer token or in the `token` query
+//! parameter for browsers and `EventSource`, which cannot set headers
+
+use {
+	super::{Aliases, Session},
+	axum::{
+		Json, Router,
+		body::Bytes,
+		extract::{Path, Query, Request, State},
+		http::{HeaderMap, HeaderValue, StatusCode, header},
+		middleware::{self, Next},
+		response::{
+			Html, IntoResponse, Response as HttpResponse,
+			sse::{self, KeepAlive, Sse},
+		},
+		routing::{get, post},
+	},
+	futures_util::{Stream, stream},
+	serde_json::Value,
+	sonas::{
+		Command,
+		event::{Push, Topic},
+		execute::SharedState,
+		instance::HttpConfig,
+		protocol::{ErrorKind, RequestBody, Response, Status},
+	},
+	sonas_parser::{
+		schema::{CommandSchema, CommandsSchema},
+		tokenizer,
+	},
+	std::{
+		net::{IpAddr, Ipv4Addr, SocketAddr},
+		sync::{Arc, PoisonError},
+	},
+	tokio::{net::TcpListener, sync::broadcast::error::RecvError},
+};
+
+pub const TOKEN_ENV: &str = "SONAS_HTTP_TOKEN";
+const DEFAULT_ADDRESS: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+const DEFAULT_PORT: u16 = 6680;
+const REMOTE: &str = include_str!("remote.html");
+
+/// Where the API is served and the token it expects
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct HttpSettings {
+	pub address: SocketAddr,
+	pub token: String,
+}
+
+impl HttpSettings {
+	/// Combines the command line with the `[daemon.http]` section and the
+	/// token from the environment, which wins over the one in the config.
+	/// Returns `None` when the API is not enabled
+	pub fn resolve(
+		enabled: bool,
+		address: Option<IpAddr>,
+		port: Option<u16>,
+		config: Option<HttpConfig>,
+		env_token: Option<String>,
+	) -> Result<Option<Self>, String> {
+		if !enabled && address.is_none() && port.is_none() && config.is_none() {
+			return Ok(None);
+		}
+		let config = config.unwrap_or_default();
+		let token = env_token
+			.or(config.token)
+			.filter(|token| !token.is_empty())
+			.ok_or_else(|| {
+				format!("the HTTP API needs a token, set it in [daemon.http] or with {TOKEN_ENV}")
+			})?;
+
+		Ok(Some(Self {
+			address: SocketAddr::new(
+				address.or(config.address).unwrap_or(DEFAULT_ADDRESS),
+				port.or(config.port).unwrap_or(DEFAULT_PORT),
+			),
+			token,
+		}))
+	}
+}
+
+#[derive(Debug, Clone)]
+struct Api {
+	state: SharedState,
+	aliases: Aliases,
+	token: Arc<str>,
+}
+
+pub async fn serve(listener: TcpListener, state: SharedState, aliases: Aliases, token: String) {
+	if let Err(e) = axum::serve(listener, router(state, aliases, token)).await {
+		eprintln!("The HTTP API stopped: {e}");
+	}
+}
+
+fn router(state: SharedState, aliases: Aliases, token: String) -> Router {
+	let api = Api {
+		state,
+		aliases,
+		token: token.into(),
+	};
+
+	Router::new()
+		.route("/", get(remote))
+		.route("/api/schema", get(schema))
+		.route("/api/events", get(events))
+		.route("/api/script", post(script))
+		.route("/api/{*command}", get(command).post(command))
+		.layer(middleware::from_fn_with_state(api.clone(), authorize))
+		.with_state(api)
+}
+
+async fn authorize(State(api): State<Api>, request: Request, next: Next) -> HttpResponse {
+	let bearer = (request.headers().get(header::AUTHORIZATION))
+		.and_then(|value| value.to_str().ok())
+		.and_then(|value| value.strip_prefix("Bearer "));
+	let query = Query::<Vec<(String, String)>>::try_from_uri(request.uri())
+		.map(|Query(query)| query)
+		.unwrap_or_default();
+	let from_query = (query.iter())
+		.find(|(key, _)| key == "token")
+		.map(|(_, token)| token.as_str());
+
+	match bearer.or(from_query) {
+		Some(token) if same_token(token, &api.token) => next.run(request).await,
+		_ => {
+			let mut headers = HeaderMap::new();
+			headers.insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+			let message = "missing or invalid token, send it as a bearer token or in ?token=\n";
+			(StatusCode::UNAUTHORIZED, headers, message).into_response()
+		}
+	}
+}
+
+/// Compares every byte, so the time taken does not tell how much of a
+/// guessed token was right
+fn same_token(given: &str, token: &str) -> bool {
+	given.len() == token.len()
+		&& (given.bytes().zip(token.bytes())).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+async fn remote() -> Html<&'static str> {
+	Html(REMOTE)
+}
+
+async fn schema() -> Json<CommandsSchema> {
+	Json(Command::SCHEMA)
+}
+
+/// Runs `/api/<category>/<subcommand>` with the arguments from the query
+/// string, or from a JSON object in the body of a POST request
+async fn command(
+	State(api): State<Api>,
+	Path(path): Path<String>,
+	Query(query): Query<Vec<(String, String)>>,
+	body: Bytes,
+) -> HttpResponse {
+	let mut arguments = (query.into_iter())
+		.filter(|(key, _)| key != "token")
+		.map(|(key, value)| (key, tokenizer::quote(&value).into_owned()))
+		.collect::<Vec<_>>();
+	if !body.is_empty() {
+		match serde_json::from_slice::<serde_json::Map<String, Value>>(&body) {
+			Ok(object) => arguments
+				.extend((object.into_iter()).map(|(key, value)| (key, argument_value(&value)))),
+			Err(e) => {
+				let message = format!("the body must be a JSON object of arguments: {e}\n");
+				return (StatusCode::BAD_REQUEST, message).into_response();
+			}
+		}
+	}
+
+	match command_line(&path, &arguments) {
+		Ok(command) => run(api, RequestBody::Command { command }).await,
+		Err(message) => (StatusCode::BAD_REQUEST, message).into_response(),
+	}
+}
+
+async fn script(State(api): State<Api>, script: String) -> HttpResponse {
+	let body = RequestBody::Script {
+		script,
+		on_error: None,
+	};
+	run(api, body).await
+}
+
+async fn run(api: Api, body: RequestBody) -> HttpResponse {
+	let aliases = super::read(&api.aliases);
+	let response = super::respond(body, &mut Session::default(), &api.state, &aliases);
+	(status_code(&response), Json(response)).into_response()
+}
+
+fn status_code(response: &Response) -> StatusCode {
+	match &response.status {
+		Status::Ok => StatusCode::OK,
+		Status::Error { errors } => match errors.first().map(|error| error.kind) {
+			Some(ErrorKind::Execute) => StatusCode::UNPROCESSABLE_ENTITY,
+			_ => StatusCode::BAD_REQUEST,
+		},
+	}
+}
+
+/// Joins the path and the arguments into a command line. Path segments and
+/// argument names have to be plain names, so they cannot smuggle in quotes,
+/// further arguments or `;` separated commands
+fn command_line(path: &str, arguments: &[(String, String)]) -> Result<String, String> {
+	let is_name = |name: &str| {
+		!name.is_empty() && (name.chars()).all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+	};
+
+	let segments = path
+		.split('/')
+		.filter(|segment| !segment.is_empty())
+		.collect::<Vec<_>>();
+	if let Some(segment) = segments.iter().find(|segment| !is_name(segment)) {
+		return Err(format!("invalid command name '{segment}'\n"));
+	}
+	let mut line = segments.join(" ");
+	for (key, value) in arguments {
+		if !is_name(key) {
+			return Err(format!("invalid argument name '{key}'\n"));
+		}
+		line += &format!(" {key}={value}");
+	}
+	Ok(line)
+}
+
+/// Renders a JSON value the way the command parser expects it, with arrays
+/// as comma separated lists
+fn argument_value(value: &Value) -> String {
+	match value {
+		Value::String(text) => tokenizer::quote(text).into_owned(),
+		Value::Array(values) => values
+			.iter()
+			.map(argument_value)
+			.collect::<Vec<_>>()
+			.join(","),
+		value => value.to_string(),
+	}
+}
+
+/// Streams the player events, only those of the topics in `?topics=` if
+/// it is given
+async fn events(
+	State(api): State<Api>,
+	Query(query): Query<Vec<(String, String)>>,
+) -> Result<Sse<impl Stream<Item = Result<sse::Event, axum::Error>>>, (StatusCode, String)> {
+	let topics = (query.iter())
+		.filter(|(key, _)| key == "topics")
+		.flat_map(|(_, topics)| topics.split(','))
+		.map(|topic| topic.parse::<Topic>())
+		.collect::<Result<Vec<_>, _>>()
+		.map_err(|e| (StatusCode::BAD_REQUEST, format!("{e}\n")))?;
+	let events = (api.state.lock().unwrap_or_else(PoisonError::into_inner))
+		.events
+		.subscribe();
+
+	let pushes = stream::unfold(events, move |mut events| {
+		let topics = topics.clone();
+		async move {
+			loop {
+				let push = match events.recv().await {
+					Ok(event) if topics.is_empty() || topics.contains(&event.topic()) => {
+						Push::Event(event)
+					}
+					Ok(_) => continue,
+					Err(RecvError::Lagged(missed)) => Push::Lagged { missed },
+					Err(RecvError::Closed) => return None,
+				};
+				return Some((server_event(&push), events));
+			}
+		}
+	});
+	Ok(Sse::new(pushes).keep_alive(KeepAlive::default()))
+}
+
+fn server_event(push: &Push) -> Result<sse::Event, axum::Error> {
+	match push {
+		Push::Event(event) => sse::Event::default()
+			.event(event.topic().to_string())
+			.json_data(event),
+		Push::Lagged { missed } => sse::Event::default()
+			.event("lagged")
+			.json_data(serde_json::json!({ "missed": missed })),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+	async fn request(address: SocketAddr, line: &str, body: &str) -> String {
+		let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+		let (line, headers) = line.split_once('\n').unwrap_or((line, ""));
+		let request = format!(
+			"{line} HTTP/1.1\r\nHost: sonas\r\nConnection: close\r\n{headers}\
+			Content-Length: {}\r\n\r\n{body}",
+			body.len()
+		);
+		stream.write_all(request.as_bytes()).await.unwrap();
+		let mut response = String::new();
+		stream.read_to_string(&mut response).await.unwrap();
+		response
+	}
+
+	#[test]
+	fn builds_command_lines() {
+		let arguments = [
+			("sort".to_string(), argument_value(&Value::from("asc"))),
+			(
+				"ids".to_string(),
+				argument_value(&serde_json::json!([1, "a b"])),
+			),
+		];
+		assert_eq!(
+			command_line("/album/list/", &arguments).as_deref(),
+			Ok(r#"album list sort=asc ids=1,"a b""#)
+		);
+		assert!(command_line("album/list sort=asc", &[]).is_err());
+		assert!(command_line("player/status;player next", &[]).is_err());
+		let injected = [("sort=asc next".to_string(), "1".to_string())];
+		assert!(command_line("album/list", &injected).is_err());
+		assert!(command_line("album/list", &[(String::new(), "1".to_string())]).is_err());
+		assert!(same_token("secret", "secret"));
+		assert!(!same_token("secreT", "secret"));
+		assert!(!same_token("secret!", "secret"));
+	}
+
+	#[test]
+	fn needs_a_token() {
+		let config = HttpConfig {
+			port: Some(80),
+			..HttpConfig::default()
+		};
+		assert_eq!(
+			HttpSettings::resolve(false, None, None, None, None),
+			Ok(None)
+		);
+		assert!(HttpSettings::resolve(true, None, None, Some(config.clone()), None).is_err());
+		let empty = Some(String::new());
+		assert!(HttpSettings::resolve(true, None, None, None, empty).is_err());
+
+		let from_env = Some("from-env".to_string());
+		let settings = HttpSettings::resolve(true, None, None, None, from_env.clone()).unwrap();
+		assert_eq!(
+			settings,
+			Some(HttpSettings {
+				address: SocketAddr::new(DEFAULT_ADDRESS, DEFAULT_PORT),
+				token: "from-env".to_string(),
+			})
+		);
+
+		let config = HttpConfig {
+			token: Some("secret".to_string()),
+			..config
+		};
+		let settings = HttpSettings::resolve(false, None, None, Some(config.clone()), None);
+		assert_eq!(
+			settings.unwrap().map(|settings| settings.address),
+			Some(SocketAddr::new(DEFAULT_ADDRESS, 80))
+		);
+		let settings = HttpSettings::resolve(false, None, None, Some(config), from_env);
+		assert_eq!(
+			settings.unwrap().map(|settings| settings.token).as_deref(),
+			Some("from-env")
+		);
+	}
+
+	#[tokio::test]
+	async fn serves_commands_to_authorized_clients() {
+		let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+		let address = listener.local_addr().unwrap();
+		let state = SharedState::default();
+		let aliases = Aliases::default();
+		tokio::spawn(serve(
+			listener,
+			state.clone(),
+			aliases,
+			"secret".to_string(),
+		));
+
+		let response = request(address, "GET /api/player/status", "").await;
+		assert!(response.starts_with("HTTP/1.1 401"));
+		let response = request(address, "GET /?token=wrong", "").await;
+		assert!(response.starts_with("HTTP/1.1 401"));
+
+		let line = "POST /api/player/volume\nAuthorization: Bearer secret\r\n";
+		let response = request(address, line, r#"{"level": "25%"}"#).await;
+		assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+		assert!(response.ends_with(r#"{"id":0,"status":"ok","results":[{"type":"ack"}]}"#));
+		assert_eq!(state.lock().unwrap().player.volume, 25);
+
+		let response = request(address, "GET /api/queue/delete?position=3&token=secret", "").await;
+		assert!(response.starts_with("HTTP/1.1 422"), "{response}");
+		let response = request(address, "GET /api/player/fly?token=secret", "").await;
+		assert!(response.starts_with("HTTP/1.1 400"), "{response}");
+		let line = "POST /api/player/volume?token=secret";
+		let response = request(address, line, r#"{"level; player stop": "5%"}"#).await;
+		assert!(response.starts_with("HTTP/1.1 400"), "{response}");
+		let response = request(address, "GET /api/player/status%3B?token=secret", "").await;
+		assert!(response.starts_with("HTTP/1.1 400"), "{response}");
+		let response = request(address, "GET /?token=secret", "").await;
+		assert!(response.contains("<title>sonas</title>"));
+	}
+}
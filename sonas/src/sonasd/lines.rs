@@ -0,0 +1,117 @@
+use {
+	std::{io, mem},
+	thiserror::Error,
+	tokio::io::{AsyncBufRead, AsyncBufReadExt},
+};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Error)]
+#[error("the line is longer than {max_len} bytes")]
+pub struct LineTooLong {
+	pub max_len: usize,
+}
+
+/// Splits a reader into lines like [`AsyncBufReadExt::lines`], but never
+/// buffers more than `max_len` bytes of a line. Reading is cancel safe, so it
+/// can be raced against other futures
+#[derive(Debug)]
+pub struct BoundedLines<R> {
+	reader: R,
+	max_len: usize,
+	line: Vec<u8>,
+	/// Whether the rest of a line that is too long is being skipped
+	skipping: bool,
+}
+
+impl<R: AsyncBufRead + Unpin> BoundedLines<R> {
+	pub fn new(reader: R, max_len: usize) -> Self {
+		Self {
+			reader,
+			max_len,
+			line: Vec::new(),
+			skipping: false,
+		}
+	}
+
+	pub fn get_mut(&mut self) -> &mut R {
+		&mut self.reader
+	}
+
+	/// Reads the next line without its line ending. A line that is too long
+	/// is skipped up to its end and returned as an error, so reading goes on
+	/// with the line after it
+	pub async fn next_line(&mut self) -> io::Result<Option<Result<String, LineTooLong>>> {
+		loop {
+			let available = self.reader.fill_buf().await?;
+			if available.is_empty() && self.line.is_empty() && !self.skipping {
+				return Ok(None);
+			}
+			// the end of the input also ends the last line
+			let (content, len, ended) = match available.iter().position(|&byte| byte == b'\n') {
+				Some(end) => (&available[..end], end + 1, true),
+				None => (available, available.len(), available.is_empty()),
+			};
+
+			if self.line.len() + content.len() > self.max_len {
+				self.line.clear();
+				self.skipping = true;
+			} else if !self.skipping {
+				self.line.extend_from_slice(content);
+			}
+			self.reader.consume(len);
+
+			if ended {
+				return Ok(Some(self.take_line()?));
+			}
+		}
+	}
+
+	fn take_line(&mut self) -> io::Result<Result<String, LineTooLong>> {
+		if mem::take(&mut self.skipping) {
+			return Ok(Err(LineTooLong {
+				max_len: self.max_len,
+			}));
+		}
+
+		let mut line = mem::take(&mut self.line);
+		if line.last() == Some(&b'\r') {
+			line.pop();
+		}
+		String::from_utf8(line)
+			.map(Ok)
+			.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test]
+	async fn splits_lines() {
+		let mut lines = BoundedLines::new("one\r\ntwo\n\nthree".as_bytes(), 8);
+		for expected in ["one", "two", "", "three"] {
+			assert_eq!(
+				lines.next_line().await.unwrap(),
+				Some(Ok(expected.to_string()))
+			);
+		}
+		assert_eq!(lines.next_line().await.unwrap(), None);
+	}
+
+	#[tokio::test]
+	async fn skips_lines_that_are_too_long() {
+		let input = format!("{}\nshort\n{}", "x".repeat(100), "y".repeat(9));
+		// a tiny buffer makes the long lines arrive in several pieces
+		let mut lines =
+			BoundedLines::new(tokio::io::BufReader::with_capacity(4, input.as_bytes()), 8);
+		let too_long = Some(Err(LineTooLong { max_len: 8 }));
+
+		assert_eq!(lines.next_line().await.unwrap(), too_long);
+		assert_eq!(
+			lines.next_line().await.unwrap(),
+			Some(Ok("short".to_string()))
+		);
+		assert_eq!(lines.next_line().await.unwrap(), too_long);
+		assert_eq!(lines.next_line().await.unwrap(), None);
+	}
+}
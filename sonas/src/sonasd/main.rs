@@ -1,49 +1,773 @@
+mod http;
+mod lines;
+mod mpd;
+mod persist;
+
 use {
+	clap::Parser,
+	http::HttpSettings,
 	interprocess::local_socket::{
-		GenericNamespaced, ListenerOptions,
-		tokio::{Stream, prelude::*},
+		ListenerOptions,
+		tokio::{SendHalf, Stream, prelude::*},
+	},
+	lines::BoundedLines,
+	sonas::{
+		Command,
+		alias::AliasTable,
+		event::{Event, Push, Topic},
+		execute::{Execute, ExecuteError, Reply, SharedState},
+		instance::{self, ClaimError, DaemonConfig, DaemonStatus, Instance},
+		library::Library,
+		paths,
+		protocol::{
+			ErrorKind, ErrorPayload, Format, Hello, PlainVerb, Request, RequestBody, Response,
+			Settings, VERSION,
+		},
+		script::{OnError, Script},
+		server::Client,
+	},
+	sonas_parser::{Arguments, ParseCommandError, tokenizer},
+	std::{
+		collections::HashSet,
+		env, fmt,
+		fs::{self, File},
+		future, io,
+		net::IpAddr,
+		path::{Path, PathBuf},
+		process::{self, Stdio},
+		sync::{Arc, PoisonError, RwLock, RwLockReadGuard},
+		thread,
+		time::{Duration, Instant},
+	},
+	tokio::{
+		io::{AsyncBufRead, AsyncReadExt, AsyncWriteExt, BufReader},
+		net::TcpListener,
+		sync::{
+			Notify,
+			broadcast::{self, error::RecvError},
+		},
+		time,
 	},
-	sonas::Command,
-	std::io,
-	tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
 };
 
+const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+const READY_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_SCRIPT_LEN: usize = 4 * 1024 * 1024;
+/// JSON requests carry their script on the request line
+const MAX_LINE_LEN: usize = 2 * MAX_SCRIPT_LEN;
+
+/// The aliases of the config file, shared so they can be reloaded
+type Aliases = Arc<RwLock<AliasTable>>;
+
+/// Background daemon of sonas, controlled through sonasctl
+#[derive(Parser, Debug)]
+#[command(version)]
+struct Args {
+	/// Socket to listen on, either a namespaced name or a file path [env: SONAS_SOCKET]
+	#[arg(long, value_name = "PATH")]
+	socket: Option<String>,
+
+	/// Name of this instance, so several daemons can run side by side [env: SONAS_INSTANCE]
+	#[arg(
+		long,
+		value_name = "NAME",
+		value_parser = instance::parse_name,
+		conflicts_with = "socket"
+	)]
+	instance: Option<String>,
+
+	/// Detach from the terminal and keep running in the background
+	#[arg(long, conflicts_with = "foreground")]
+	daemonize: bool,
+
+	/// Stay attached to the terminal, this is the default
+	#[arg(long)]
+	foreground: bool,
+
+	/// File the output goes to when daemonized, next to the pid file by default
+	#[arg(long, value_name = "PATH", requires = "daemonize")]
+	log: Option<PathBuf>,
+
+	/// Also accept MPD clients on this TCP address, like 127.0.0.1:6600
+	#[arg(long, value_name = "ADDR")]
+	mpd: Option<String>,
+
+	/// Serve the HTTP API and the web remote, also enabled by a [daemon.http]
+	/// config section. The token is read from there or from SONAS_HTTP_TOKEN
+	#[arg(long)]
+	http: bool,
+
+	/// Address the HTTP API listens on [default: 127.0.0.1]
+	#[arg(long, value_name = "ADDR")]
+	http_address: Option<IpAddr>,
+
+	/// Port the HTTP API listens on [default: 6680]
+	#[arg(long, value_name = "PORT")]
+	http_port: Option<u16>,
+}
+
+impl Args {
+	/// The options the daemonized process has to be started with again
+	fn forwarded(&self) -> Vec<String> {
+		let mut args = Vec::new();
+		if let Some(address) = &self.mpd {
+			args.extend(["--mpd".to_string(), address.clone()]);
+		}
+		if self.http {
+			args.push("--http".to_string());
+		}
+		if let Some(address) = self.http_address {
+			args.extend(["--http-address".to_string(), address.to_string()]);
+		}
+		if let Some(port) = self.http_port {
+			args.extend(["--http-port".to_string(), port.to_string()]);
+		}
+		args
+	}
+}
+
+#[derive(Debug, Default)]
+struct Session {
+	format: Format,
+	on_error: OnError,
+	subscription: Option<Subscription>,
+	shutdown: bool,
+}
+
+#[derive(Debug)]
+struct Subscription {
+	events: broadcast::Receiver<Event>,
+	topics: HashSet<Topic>,
+}
+
+impl Subscription {
+	async fn next(subscription: &mut Option<Self>) -> Push {
+		let Some(subscription) = subscription else {
+			return future::pending().await;
+		};
+
+		loop {
+			match subscription.events.recv().await {
+				Ok(event) if subscription.topics.contains(&event.topic()) => {
+					return Push::Event(event);
+				}
+				Ok(_) => (),
+				Err(RecvError::Lagged(missed)) => return Push::Lagged { missed },
+				Err(RecvError::Closed) => return future::pending().await,
+			}
+		}
+	}
+}
+
 #[tokio::main]
 async fn main() -> io::Result<()> {
-	let printname = "sonasd.sock";
-	let name = printname.to_ns_name::<GenericNamespaced>()?;
+	let args = Args::parse();
+	let forwarded = args.forwarded();
+	let config = (paths::config_path().map(|path| DaemonConfig::load(&path)))
+		.transpose()
+		.unwrap_or_else(|e| exit(format!("failed to load the config: {e}")))
+		.unwrap_or_default();
+	let http = HttpSettings::resolve(
+		args.http,
+		args.http_address,
+		args.http_port,
+		config.http,
+		env::var(http::TOKEN_ENV).ok(),
+	)
+	.unwrap_or_else(|e| exit(e));
+	let instance = Instance::resolve(args.socket, args.instance).unwrap_or_else(|e| exit(e));
+	if args.daemonize {
+		daemonize(&instance, args.log, &forwarded).unwrap_or_else(|e| exit(e));
+		return Ok(());
+	}
 
-	let opts = ListenerOptions::new().name(name);
-	let listener = opts.create_tokio()?;
+	let _lock = instance.claim().unwrap_or_else(|e| exit(e));
+	let listener = match ListenerOptions::new().name(instance.name()?).create_tokio() {
+		Err(e) if e.kind() == io::ErrorKind::AddrInUse => exit(ClaimError::Running {
+			socket: instance.socket,
+			pid: None,
+		}),
+		listener => listener?,
+	};
+	eprintln!("Listening on {}", instance.socket);
+
+	let shutdown = Arc::new(Notify::new());
+	let state_path = instance.state_path();
+	let library_path = config.library.unwrap_or_else(paths::library_path);
+	let state = initial_state(&library_path, &state_path);
+	let aliases = Aliases::new(RwLock::new(load_aliases().unwrap_or_default()));
+	run_rc_file(&state, &read(&aliases));
+
+	#[cfg(unix)]
+	tokio::spawn(reload_on_hangup(
+		aliases.clone(),
+		state.clone(),
+		library_path,
+	));
+
+	tokio::spawn(persist::save_on_change(state.clone(), state_path.clone()));
+	if let Some(address) = args.mpd.or(config.mpd) {
+		let listener = TcpListener::bind(&address).await.unwrap_or_else(|e| {
+			exit(format!(
+				"failed to listen for MPD clients on {address}: {e}"
+			))
+		});
+		eprintln!("Listening for MPD clients on {}", listener.local_addr()?);
+		tokio::spawn(mpd::serve(listener, state.clone()));
+	}
+	if let Some(settings) = http {
+		let listener = TcpListener::bind(settings.address)
+			.await
+			.unwrap_or_else(|e| exit(format!("failed to serve HTTP on {}: {e}", settings.address)));
+		eprintln!("Serving the HTTP API on http://{}/", listener.local_addr()?);
+		tokio::spawn(http::serve(
+			listener,
+			state.clone(),
+			aliases.clone(),
+			settings.token,
+		));
+	}
+	let terminated = persist::terminated();
+	tokio::pin!(terminated);
 
 	loop {
-		let conn = match listener.accept().await {
-			Ok(c) => c,
-			Err(e) => {
-				eprintln!("There was an error with an incoming connection: {e}");
-				continue;
+		let conn = tokio::select! {
+			conn = listener.accept() => match conn {
+				Ok(c) => c,
+				Err(e) => {
+					eprintln!("There was an error with an incoming connection: {e}");
+					continue;
+				}
+			},
+			() = shutdown.notified() => break,
+			result = &mut terminated => {
+				result?;
+				break;
 			}
 		};
 
+		let state = state.clone();
+		let aliases = aliases.clone();
+		let shutdown = shutdown.clone();
 		tokio::spawn(async move {
-			if let Err(e) = handle_conn(conn).await {
+			if let Err(e) = handle_conn(conn, &state, &aliases, &shutdown).await {
 				eprintln!("Error while handling connection: {e}");
 			}
 		});
 	}
+
+	eprintln!("Shutting down");
+	persist::save(&state, &state_path);
+	Ok(())
 }
 
-async fn handle_conn(conn: Stream) -> io::Result<()> {
-	let mut recver = BufReader::new(&conn);
-	let mut sender = &conn;
+/// Starts this binary again in the foreground as the leader of a new session
+/// with its output going to the log file, and returns once it accepts
+/// connections
+fn daemonize(instance: &Instance, log: Option<PathBuf>, forwarded: &[String]) -> io::Result<()> {
+	if let DaemonStatus::Running { pid } = instance.status()? {
+		exit(ClaimError::Running {
+			socket: instance.socket.clone(),
+			pid,
+		});
+	}
 
-	let mut buf = String::with_capacity(128);
-	let _ = recver.read_line(&mut buf).await?;
+	let log_path = log.unwrap_or_else(|| instance.log_path());
+	if let Some(parent) = log_path.parent() {
+		fs::create_dir_all(parent)?;
+	}
+	let log = File::options().create(true).append(true).open(&log_path)?;
 
-	let result = match buf.parse::<Command>() {
-		Ok(command) => format!("{:?}", command),
-		Err(error) => format!("{:?}", error),
+	let mut command = process::Command::new(env::current_exe()?);
+	command
+		.args(["--foreground", "--socket", &instance.socket])
+		.args(forwarded)
+		.stdin(Stdio::null())
+		.stdout(log.try_clone()?)
+		.stderr(log);
+	#[cfg(unix)]
+	{
+		use std::os::unix::process::CommandExt as _;
+		// SAFETY: setsid is async-signal-safe and only affects the child
+		unsafe {
+			command.pre_exec(|| match libc::setsid() {
+				-1 => Err(io::Error::last_os_error()),
+				_ => Ok(()),
+			});
+		}
+	}
+	#[cfg(windows)]
+	{
+		use std::os::windows::process::CommandExt as _;
+		const DETACHED_PROCESS: u32 = 0x8;
+		const CREATE_NEW_PROCESS_GROUP: u32 = 0x200;
+		command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+	}
+	let mut child = command.spawn()?;
+
+	let started = Instant::now();
+	while started.elapsed() < READY_TIMEOUT {
+		if let Some(status) = child.try_wait()? {
+			return Err(io::Error::other(format!(
+				"sonasd exited with {status}, see {}",
+				log_path.display()
+			)));
+		}
+		if Client::connect_to(instance.name()?).is_ok() {
+			println!("sonasd started on {} (pid {})", instance.socket, child.id());
+			return Ok(());
+		}
+		thread::sleep(Duration::from_millis(50));
+	}
+	Err(io::Error::new(
+		io::ErrorKind::TimedOut,
+		format!("sonasd did not start in time, see {}", log_path.display()),
+	))
+}
+
+fn exit(error: impl fmt::Display) -> ! {
+	eprintln!("error: {error}");
+	process::exit(1)
+}
+
+/// Builds the state sonasd starts with, the albums from the library file and
+/// the player as it was left by the last run
+fn initial_state(library_path: &Path, state_path: &Path) -> SharedState {
+	let state = SharedState::default();
+	if let Some(library) = load_library(library_path) {
+		let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+		state.set_library(library);
+	}
+	persist::restore(&state, state_path);
+	state
+}
+
+fn load_library(path: &Path) -> Option<Library> {
+	match Library::load(path) {
+		Ok(library) => Some(library),
+		Err(e) => {
+			eprintln!("Failed to load the library from {}: {e}", path.display());
+			None
+		}
+	}
+}
+
+fn load_aliases() -> Option<AliasTable> {
+	let Some(path) = paths::config_path() else {
+		return Some(AliasTable::default());
+	};
+
+	let aliases = match AliasTable::load(&path) {
+		Ok(aliases) => aliases,
+		Err(e) => {
+			eprintln!("Failed to load aliases: {e}");
+			return None;
+		}
+	};
+	if let Err(e) = aliases.validate() {
+		eprintln!("Invalid aliases: {e}");
+		return None;
+	}
+	Some(aliases)
+}
+
+/// Loads the aliases and the library again whenever sonasd receives SIGHUP,
+/// keeping the current ones if their file is broken
+#[cfg(unix)]
+async fn reload_on_hangup(
+	aliases: Aliases,
+	state: SharedState,
+	library_path: PathBuf,
+) -> io::Result<()> {
+	use tokio::signal::unix::{SignalKind, signal};
+
+	let mut hangup = signal(SignalKind::hangup())?;
+	while hangup.recv().await.is_some() {
+		if let Some(reloaded) = load_aliases() {
+			*aliases.write().unwrap_or_else(PoisonError::into_inner) = reloaded;
+			eprintln!("Reloaded the aliases");
+		}
+		if let Some(library) = load_library(&library_path) {
+			let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+			state.set_library(library);
+			eprintln!("Reloaded the library");
+		}
+	}
+	Ok(())
+}
+
+fn run_rc_file(state: &SharedState, aliases: &AliasTable) {
+	let Some(path) = paths::rc_path().filter(|path| path.exists()) else {
+		return;
 	};
 
-	sender.write_all(&result.into_bytes()).await
+	match fs::read_to_string(&path) {
+		Ok(script) => {
+			let body = RequestBody::Script {
+				script,
+				on_error: Some(OnError::Continue),
+			};
+			let response = respond(body, &mut Session::default(), state, aliases);
+			eprint!("{response}");
+		}
+		Err(e) => eprintln!("Failed to read {}: {e}", path.display()),
+	}
+}
+
+async fn handle_conn(
+	conn: Stream,
+	state: &SharedState,
+	aliases: &Aliases,
+	shutdown: &Notify,
+) -> io::Result<()> {
+	let (recver, mut sender) = conn.split();
+	let mut lines = BoundedLines::new(BufReader::new(recver), MAX_LINE_LEN);
+	let mut session = Session::default();
+
+	loop {
+		// subscribers are expected to sit idle while waiting for events
+		let timeout = match session.subscription {
+			Some(_) => Duration::MAX,
+			None => IDLE_TIMEOUT,
+		};
+		let line = tokio::select! {
+			line = time::timeout(timeout, lines.next_line()) => match line {
+				Ok(Ok(Some(Ok(line)))) => line,
+				Ok(Ok(Some(Err(too_long)))) => {
+					let error = ErrorPayload::new(ErrorKind::Protocol, too_long.to_string());
+					write_response(&mut sender, session.format, &Response::error(0, error)).await?;
+					continue;
+				}
+				Ok(Ok(None)) | Err(_) => return Ok(()),
+				Ok(Err(e)) => return Err(e),
+			},
+			push = Subscription::next(&mut session.subscription) => {
+				match session.format {
+					Format::Json => write_json(&mut sender, &push).await?,
+					Format::Plain => sender.write_all(push.to_string().as_bytes()).await?,
+				}
+				continue;
+			}
+		};
+		let input = line.trim();
+		if input.is_empty() {
+			continue;
+		}
+
+		let response = if let Ok(hello) = serde_json::from_str::<Hello>(input) {
+			session.format = Format::Json;
+			write_json(&mut sender, &Hello::new("sonasd")).await?;
+			if hello.version == VERSION {
+				continue;
+			}
+
+			let error = ErrorPayload::new(
+				ErrorKind::Version,
+				format!(
+					"unsupported protocol version {}, expected {VERSION}",
+					hello.version
+				),
+			);
+			return write_json(&mut sender, &Response::error(0, error)).await;
+		} else if input.starts_with('{') {
+			match serde_json::from_str::<Request>(input) {
+				Ok(request) => Response {
+					id: request.id,
+					..respond(request.body, &mut session, state, &read(aliases))
+				},
+				Err(e) => {
+					let id = serde_json::from_str::<serde_json::Value>(input)
+						.ok()
+						.and_then(|value| value.get("id")?.as_u64())
+						.unwrap_or_default();
+					let error =
+						ErrorPayload::new(ErrorKind::Protocol, format!("invalid request: {e}"));
+					Response::error(id, error)
+				}
+			}
+		} else {
+			match plain_request(input.to_string(), &mut lines).await? {
+				Ok(body) => respond(body, &mut session, state, &read(aliases)),
+				Err(error) => Response::error(0, error),
+			}
+		};
+
+		write_response(&mut sender, session.format, &response).await?;
+		if session.shutdown {
+			shutdown.notify_one();
+			return Ok(());
+		}
+	}
+}
+
+async fn plain_request<R: AsyncBufRead + Unpin>(
+	input: String,
+	lines: &mut BoundedLines<R>,
+) -> io::Result<Result<RequestBody, ErrorPayload>> {
+	let (name, rest) = input
+		.split_once(char::is_whitespace)
+		.unwrap_or((&input, ""));
+	Ok(Ok(match (PlainVerb::from_name(name), rest) {
+		(Some(PlainVerb::Help), path) => RequestBody::Help {
+			path: path.to_string(),
+		},
+		(Some(PlainVerb::Shutdown), "") => RequestBody::Shutdown,
+		(Some(PlainVerb::Set), settings) => match parse_settings(settings) {
+			Ok(settings) => RequestBody::Set(settings),
+			Err(error) => return Ok(Err(ErrorPayload::parse(&error, settings))),
+		},
+		(Some(verb @ (PlainVerb::Subscribe | PlainVerb::Unsubscribe)), topics) => {
+			match parse_topics(topics) {
+				Ok(topics) if verb == PlainVerb::Subscribe => RequestBody::Subscribe { topics },
+				Ok(topics) => RequestBody::Unsubscribe { topics },
+				Err(error) => return Ok(Err(ErrorPayload::parse(&error, topics))),
+			}
+		}
+		(Some(PlainVerb::Script), header) => {
+			let Some((on_error, len)) = header
+				.split_once(' ')
+				.and_then(|(on_error, len)| Some((on_error.parse().ok()?, len.parse().ok()?)))
+			else {
+				let message = format!("invalid script header '{header}'");
+				return Ok(Err(ErrorPayload::new(ErrorKind::Protocol, message)));
+			};
+			if len > MAX_SCRIPT_LEN {
+				// skip the script so the next request is read from the right place
+				let mut script = lines.get_mut().take(len as u64);
+				tokio::io::copy(&mut script, &mut tokio::io::sink()).await?;
+				let message =
+					format!("the script is {len} bytes long, at most {MAX_SCRIPT_LEN} are allowed");
+				return Ok(Err(ErrorPayload::new(ErrorKind::Protocol, message)));
+			}
+
+			let mut text = vec![0; len];
+			lines.get_mut().read_exact(&mut text).await?;
+			RequestBody::Script {
+				script: String::from_utf8_lossy(&text).into_owned(),
+				on_error: Some(on_error),
+			}
+		}
+		_ => RequestBody::Command { command: input },
+	}))
+}
+
+fn parse_settings(input: &str) -> Result<Settings, ParseCommandError> {
+	let args = Arguments::parse(input, &["format", "on-error"], &[], &[], None)?;
+	Ok(Settings {
+		format: args.get_optional("format")?,
+		on_error: args.get_optional("on-error")?,
+	})
+}
+
+fn parse_topics(input: &str) -> Result<Vec<Topic>, ParseCommandError> {
+	Arguments::parse(input, &["topics"], &[], &[], Some("topics"))?.get_all("topics")
+}
+
+async fn write_json(sender: &mut SendHalf, value: &impl serde::Serialize) -> io::Result<()> {
+	let mut line = serde_json::to_string(value)?;
+	line.push('\n');
+	sender.write_all(line.as_bytes()).await
+}
+
+async fn write_response(
+	sender: &mut SendHalf,
+	format: Format,
+	response: &Response,
+) -> io::Result<()> {
+	match format {
+		Format::Json => write_json(sender, response).await,
+		Format::Plain => sender.write_all(response.to_string().as_bytes()).await,
+	}
+}
+
+fn read(aliases: &Aliases) -> RwLockReadGuard<'_, AliasTable> {
+	aliases.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn respond(
+	body: RequestBody,
+	session: &mut Session,
+	state: &SharedState,
+	aliases: &AliasTable,
+) -> Response {
+	match body {
+		RequestBody::Set(settings) => {
+			session.format = settings.format.unwrap_or(session.format);
+			session.on_error = settings.on_error.unwrap_or(session.on_error);
+			Response::new(0, vec![Reply::Ack], Vec::new())
+		}
+		RequestBody::Subscribe { topics } => {
+			let subscription = session.subscription.get_or_insert_with(|| Subscription {
+				events: state
+					.lock()
+					.unwrap_or_else(PoisonError::into_inner)
+					.events
+					.subscribe(),
+				topics: HashSet::new(),
+			});
+			match topics.is_empty() {
+				true => subscription.topics.extend(Topic::ALL),
+				false => subscription.topics.extend(topics),
+			}
+			Response::new(0, vec![Reply::Ack], Vec::new())
+		}
+		RequestBody::Unsubscribe { topics } => {
+			if let Some(subscription) = &mut session.subscription {
+				subscription
+					.topics
+					.retain(|topic| !topics.is_empty() && !topics.contains(topic));
+				if subscription.topics.is_empty() {
+					session.subscription = None;
+				}
+			}
+			Response::new(0, vec![Reply::Ack], Vec::new())
+		}
+		RequestBody::Shutdown => {
+			session.shutdown = true;
+			Response::new(0, vec![Reply::Ack], Vec::new())
+		}
+		RequestBody::Help { path } => match Command::help(&path) {
+			Ok(help) => Response::new(0, vec![Reply::Help(help)], Vec::new()),
+			Err(error) => Response::error(0, parse_error(&error, &path)),
+		},
+		RequestBody::Script { script, on_error } => {
+			let on_error = on_error.unwrap_or(session.on_error);
+			run_script(&script, aliases, on_error, state)
+		}
+		RequestBody::Command { command } => {
+			let (name, _, _) = tokenizer::split_word(&command);
+			if aliases.contains(name) {
+				return run_script(&command, aliases, OnError::Stop, state);
+			}
+
+			match command.parse::<Command>() {
+				Ok(parsed) => match execute(parsed, state) {
+					Ok(reply) => Response::new(0, vec![reply], Vec::new()),
+					Err(error) => Response::error(0, ErrorPayload::from(&error)),
+				},
+				Err(error) => Response::error(0, parse_error(&error, &command)),
+			}
+		}
+	}
+}
+
+fn parse_error(error: &ParseCommandError, input: &str) -> ErrorPayload {
+	let mut error = ErrorPayload::parse(error, input);
+	error.diagnostic = format!("{}\n{}", error.diagnostic, Command::usage(input));
+	error
+}
+
+fn run_script(
+	text: &str,
+	aliases: &AliasTable,
+	on_error: OnError,
+	state: &SharedState,
+) -> Response {
+	let mut results = Vec::new();
+	let mut errors = Vec::new();
+
+	Script::parse(text).run(aliases, on_error, |line, command| {
+		let result = match command {
+			Ok(command) => {
+				execute(command, state).map_err(|error| ErrorPayload::from(&error).at_line(line))
+			}
+			Err(error) => Err(ErrorPayload::script(&error)),
+		};
+		match result {
+			Ok(reply) => {
+				results.push(reply);
+				true
+			}
+			Err(error) => {
+				errors.push(error);
+				false
+			}
+		}
+	});
+
+	Response::new(0, results, errors)
+}
+
+fn execute(command: Command, state: &SharedState) -> Result<Reply, ExecuteError> {
+	let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+	command.execute(&mut state)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sonas::execute::AlbumSummary;
+
+	#[test]
+	fn starts_with_the_albums_of_the_library_file() {
+		let dir = env::temp_dir().join(format!("sonasd-library-{}", process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let library_path = dir.join("library.toml");
+		fs::write(
+			&library_path,
+			r#"
+			[[albums]]
+			id = 7
+			title = "Blue"
+			artist = "Joni Mitchell"
+			tracks = [{ number = 1, title = "All I Want", duration = 214 }]
+			"#,
+		)
+		.unwrap();
+
+		let state = initial_state(&library_path, &dir.join("missing.state.json"));
+		let body = RequestBody::Command {
+			command: "album list".to_string(),
+		};
+		let response = respond(
+			body,
+			&mut Session::default(),
+			&state,
+			&AliasTable::default(),
+		);
+		fs::remove_dir_all(&dir).unwrap();
+		assert_eq!(
+			response.results,
+			[Reply::Albums(vec![AlbumSummary {
+				id: 7,
+				title: "Blue".to_string(),
+				artist: "Joni Mitchell".to_string(),
+				year: None,
+				track_count: 1,
+			}])]
+		);
+	}
+
+	#[tokio::test]
+	async fn skips_scripts_that_are_too_long() {
+		let len = MAX_SCRIPT_LEN + 1;
+		let input = format!("{}player status\n", "#".repeat(len));
+		let mut lines = BoundedLines::new(input.as_bytes(), MAX_LINE_LEN);
+
+		let error = plain_request(format!("script stop {len}"), &mut lines)
+			.await
+			.unwrap()
+			.unwrap_err();
+		assert_eq!(error.kind, ErrorKind::Protocol);
+
+		let line = lines.next_line().await.unwrap().unwrap();
+		assert_eq!(line, Ok("player status".to_string()));
+	}
+
+	#[tokio::test]
+	async fn reads_scripts_after_the_header() {
+		let mut lines = BoundedLines::new("player stop\nplayer next\n".as_bytes(), MAX_LINE_LEN);
+		let request = plain_request("script continue 12".to_string(), &mut lines).await;
+		assert_eq!(
+			request.unwrap().unwrap(),
+			RequestBody::Script {
+				script: "player stop\n".to_string(),
+				on_error: Some(OnError::Continue),
+			}
+		);
+		assert_eq!(
+			lines.next_line().await.unwrap(),
+			Some(Ok("player next".to_string()))
+		);
+	}
 }
@@ -0,0 +1,1118 @@
+//! A practical subset of the MPD protocol, so that existing MPD clients can
+//! control sonasd. Every command is translated into a sonas command, song ids
+//! are the ids of the queue entries and songs are addressed as `album/track`
+
+use {
+	crate::lines::BoundedLines,
+	sonas::{
+		AlbumCommand, Command, PlayerCommand, QueueCommand,
+		event::{Event, PlaybackState},
+		execute::{Execute, ExecuteError, PlayerStatus, Reply, SharedState, Song, State},
+		player::RepeatMode,
+	},
+	sonas_parser::{Percentage, Relative, TimeOffset, Timestamp},
+	std::{
+		collections::BTreeSet,
+		fmt, io,
+		str::FromStr,
+		sync::{MutexGuard, PoisonError},
+	},
+	tokio::{
+		io::{AsyncWriteExt, BufReader},
+		net::{TcpListener, TcpStream, tcp::OwnedReadHalf},
+		sync::broadcast::{
+			self,
+			error::{RecvError, TryRecvError},
+		},
+	},
+};
+
+/// The protocol version announced to clients
+const VERSION: &str = "0.23.0";
+const MAX_LINE_LEN: usize = 64 * 1024;
+/// How many bytes of commands a command list may buffer, like MPD's
+/// `max_command_list_size`
+const MAX_LIST_SIZE: usize = 2 * 1024 * 1024;
+
+const COMMANDS: &[&str] = &[
+	"add",
+	"clear",
+	"close",
+	"command_list_begin",
+	"command_list_end",
+	"command_list_ok_begin",
+	"commands",
+	"currentsong",
+	"delete",
+	"deleteid",
+	"find",
+	"idle",
+	"list",
+	"move",
+	"next",
+	"noidle",
+	"notcommands",
+	"pause",
+	"ping",
+	"play",
+	"playid",
+	"playlistid",
+	"playlistinfo",
+	"previous",
+	"random",
+	"repeat",
+	"search",
+	"seek",
+	"seekcur",
+	"seekid",
+	"setvol",
+	"single",
+	"stats",
+	"status",
+	"stop",
+	"tagtypes",
+	"volume",
+];
+
+const TAG_TYPES: &[&str] = &["Artist", "AlbumArtist", "Album", "Title", "Track", "Date"];
+
+/// The error codes of `ACK` responses
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum AckCode {
+	NotList = 1,
+	Arg = 2,
+	Unknown = 5,
+	NoExist = 50,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Ack {
+	code: AckCode,
+	message: String,
+}
+
+impl Ack {
+	fn new(code: AckCode, message: impl fmt::Display) -> Self {
+		Self {
+			code,
+			message: message.to_string(),
+		}
+	}
+
+	fn arg(message: impl fmt::Display) -> Self {
+		Self::new(AckCode::Arg, message)
+	}
+
+	fn render(&self, index: usize, command: &str) -> String {
+		format!(
+			"ACK [{}@{index}] {{{command}}} {}\n",
+			self.code as u8, self.message
+		)
+	}
+}
+
+impl From<ExecuteError> for Ack {
+	fn from(error: ExecuteError) -> Self {
+		let code = match error {
+			ExecuteError::NoSuchPosition(_) => AckCode::Arg,
+			_ => AckCode::NoExist,
+		};
+		Self::new(code, error)
+	}
+}
+
+/// The parts of the player an `idle` client can wait for
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+enum Subsystem {
+	Database,
+	Playlist,
+	Player,
+	Mixer,
+	Options,
+}
+
+impl Subsystem {
+	const ALL: [Self; 5] = [
+		Self::Database,
+		Self::Playlist,
+		Self::Player,
+		Self::Mixer,
+		Self::Options,
+	];
+
+	fn name(self) -> &'static str {
+		match self {
+			Self::Database => "database",
+			Self::Playlist => "playlist",
+			Self::Player => "player",
+			Self::Mixer => "mixer",
+			Self::Options => "options",
+		}
+	}
+
+	fn of(event: &Event) -> Self {
+		match event {
+			Event::PlayerState { .. } | Event::CurrentTrack { .. } => Self::Player,
+			Event::Queue { .. } => Self::Playlist,
+			Event::Library { .. } => Self::Database,
+			Event::Volume { .. } => Self::Mixer,
+			Event::Options { .. } => Self::Options,
+		}
+	}
+}
+
+/// The commands of an open command list
+#[derive(Debug, Default)]
+struct CommandList {
+	commands: Vec<String>,
+	size: usize,
+	/// Whether each command is acknowledged with `list_OK`
+	list_ok: bool,
+	/// Reported instead of running the list once it ends
+	error: Option<Ack>,
+}
+
+impl CommandList {
+	fn push(&mut self, line: &str) {
+		self.size += line.len();
+		if self.size > MAX_LIST_SIZE {
+			self.fail(Ack::arg(format!(
+				"command list is larger than {MAX_LIST_SIZE} bytes"
+			)));
+		}
+		if self.error.is_none() {
+			self.commands.push(line.to_string());
+		}
+	}
+
+	/// Drops the commands, none of them may run once one is lost
+	fn fail(&mut self, ack: Ack) {
+		self.commands.clear();
+		self.error.get_or_insert(ack);
+	}
+
+	fn run(&self, state: &SharedState) -> String {
+		match &self.error {
+			Some(ack) => ack.render(0, ""),
+			None => run_list(&self.commands, self.list_ok, state),
+		}
+	}
+}
+
+/// The subsystems that changed since the client last went idle
+struct Changes {
+	events: broadcast::Receiver<Event>,
+	pending: BTreeSet<Subsystem>,
+}
+
+impl Changes {
+	fn record(&mut self, event: Result<Event, RecvError>) {
+		match event {
+			Ok(event) => {
+				self.pending.insert(Subsystem::of(&event));
+			}
+			Err(RecvError::Lagged(_)) => self.pending.extend(Subsystem::ALL),
+			Err(RecvError::Closed) => (),
+		}
+	}
+
+	fn collect(&mut self) {
+		loop {
+			let event = match self.events.try_recv() {
+				Ok(event) => Ok(event),
+				Err(TryRecvError::Lagged(missed)) => Err(RecvError::Lagged(missed)),
+				Err(TryRecvError::Empty | TryRecvError::Closed) => return,
+			};
+			self.record(event);
+		}
+	}
+
+	/// Takes the pending changes among `wanted`, all of them if it is empty
+	fn take(&mut self, wanted: &[Subsystem]) -> Vec<Subsystem> {
+		let taken = (self.pending.iter().copied())
+			.filter(|subsystem| wanted.is_empty() || wanted.contains(subsystem))
+			.collect::<Vec<_>>();
+		for subsystem in &taken {
+			self.pending.remove(subsystem);
+		}
+		taken
+	}
+}
+
+pub async fn serve(listener: TcpListener, state: SharedState) {
+	loop {
+		let stream = match listener.accept().await {
+			Ok((stream, _)) => stream,
+			Err(e) => {
+				eprintln!("There was an error with an incoming MPD connection: {e}");
+				continue;
+			}
+		};
+
+		let state = state.clone();
+		tokio::spawn(async move {
+			if let Err(e) = handle_conn(stream, &state).await {
+				eprintln!("Error while handling MPD connection: {e}");
+			}
+		});
+	}
+}
+
+async fn handle_conn(stream: TcpStream, state: &SharedState) -> io::Result<()> {
+	let (recver, mut sender) = stream.into_split();
+	let mut lines = BoundedLines::new(BufReader::new(recver), MAX_LINE_LEN);
+	let mut changes = Changes {
+		events: lock(state).events.subscribe(),
+		pending: BTreeSet::new(),
+	};
+	let mut list: Option<CommandList> = None;
+
+	sender
+		.write_all(format!("OK MPD {VERSION}\n").as_bytes())
+		.await?;
+
+	while let Some(line) = lines.next_line().await? {
+		changes.collect();
+		let line = match line {
+			Ok(line) => line,
+			Err(too_long) => {
+				match &mut list {
+					Some(open) => open.fail(Ack::arg(too_long)),
+					None => {
+						let ack = Ack::arg(too_long).render(0, "");
+						sender.write_all(ack.as_bytes()).await?;
+					}
+				}
+				continue;
+			}
+		};
+		let line = line.trim();
+		let (name, _) = line.split_once(' ').unwrap_or((line, ""));
+
+		if let Some(open) = &mut list {
+			if name != "command_list_end" {
+				open.push(line);
+				continue;
+			}
+			let response = open.run(state);
+			list = None;
+			sender.write_all(response.as_bytes()).await?;
+			continue;
+		}
+
+		let response = match name {
+			"" => continue,
+			"close" => return Ok(()),
+			"command_list_begin" | "command_list_ok_begin" => {
+				list = Some(CommandList {
+					list_ok: name == "command_list_ok_begin",
+					..CommandList::default()
+				});
+				continue;
+			}
+			"command_list_end" => {
+				Ack::new(AckCode::NotList, "not in a command list").render(0, name)
+			}
+			"idle" => match parse_subsystems(line) {
+				Ok(wanted) => match idle(&wanted, &mut changes, &mut lines).await? {
+					Some(changed) => changed,
+					None => return Ok(()),
+				},
+				Err(ack) => ack.render(0, name),
+			},
+			// a stray `noidle` after the changes were already sent
+			"noidle" => continue,
+			_ => match run(line, state) {
+				Ok(output) => output + "OK\n",
+				Err(ack) => ack.render(0, name),
+			},
+		};
+		sender.write_all(response.as_bytes()).await?;
+	}
+	Ok(())
+}
+
+/// Waits for one of the `wanted` subsystems to change or for `noidle`, and
+/// returns the response. Returns `None` when the client hung up or sent
+/// anything else, which is not allowed while idle
+async fn idle(
+	wanted: &[Subsystem],
+	changes: &mut Changes,
+	lines: &mut BoundedLines<BufReader<OwnedReadHalf>>,
+) -> io::Result<Option<String>> {
+	let mut changed = changes.take(wanted);
+	while changed.is_empty() {
+		tokio::select! {
+			event = changes.events.recv() => {
+				changes.record(event);
+				changed = changes.take(wanted);
+			}
+			line = lines.next_line() => match line? {
+				Some(Ok(line)) if line.trim() == "noidle" => break,
+				_ => return Ok(None),
+			},
+		}
+	}
+
+	let mut response = changed
+		.into_iter()
+		.map(|subsystem| format!("changed: {}\n", subsystem.name()))
+		.collect::<String>();
+	response.push_str("OK\n");
+	Ok(Some(response))
+}
+
+fn parse_subsystems(line: &str) -> Result<Vec<Subsystem>, Ack> {
+	split_args(line)?
+		.iter()
+		.skip(1)
+		.map(|name| {
+			Subsystem::ALL
+				.into_iter()
+				.find(|subsystem| subsystem.name() == name)
+				.ok_or_else(|| Ack::arg(format!("Unrecognized idle event: {name}")))
+		})
+		.collect()
+}
+
+fn run_list(commands: &[String], list_ok: bool, state: &SharedState) -> String {
+	let mut response = String::new();
+	for (index, line) in commands.iter().enumerate() {
+		match run(line, state) {
+			Ok(output) => {
+				response.push_str(&output);
+				if list_ok {
+					response.push_str("list_OK\n");
+				}
+			}
+			Err(ack) => {
+				let (name, _) = line.split_once(' ').unwrap_or((line, ""));
+				response.push_str(&ack.render(index, name));
+				return response;
+			}
+		}
+	}
+	response + "OK\n"
+}
+
+/// Runs a single command and returns its output without the final `OK`
+fn run(line: &str, state: &SharedState) -> Result<String, Ack> {
+	let args = split_args(line)?;
+	let Some((name, args)) = args.split_first() else {
+		return Err(Ack::new(AckCode::Unknown, "No command given"));
+	};
+	let args = args.iter().map(String::as_str).collect::<Vec<_>>();
+	let max_args = |max: usize| match args.len() > max {
+		true => Err(Ack::arg("too many arguments")),
+		false => Ok(()),
+	};
+	let ack = |command| execute(command, state).map(|_| String::new());
+
+	match (name.as_str(), args.as_slice()) {
+		("ping", []) => Ok(String::new()),
+		("commands", []) => Ok(list("command", COMMANDS.iter())),
+		("notcommands", []) => Ok(String::new()),
+		("tagtypes", _) => Ok(list("tagtype", TAG_TYPES.iter())),
+		("status", []) => Ok(format_status(&status(state)?)),
+		("currentsong", []) => Ok(status(state)?
+			.current
+			.map(|song| format_song(&song))
+			.unwrap_or_default()),
+		("stats", []) => {
+			let songs = search(state, &[], false)?;
+			let artists = songs
+				.iter()
+				.map(|song| &song.artist)
+				.collect::<BTreeSet<_>>();
+			let albums = songs.iter().map(|song| song.album).collect::<BTreeSet<_>>();
+			let playtime = songs
+				.iter()
+				.map(|song| song.track.duration.as_secs())
+				.sum::<u64>();
+			Ok(format!(
+				"artists: {}\nalbums: {}\nsongs: {}\ndb_playtime: {playtime}\n",
+				artists.len(),
+				albums.len(),
+				songs.len()
+			))
+		}
+
+		(name @ ("play" | "playid"), [..]) => {
+			max_args(1)?;
+			let position = match (name, args.first()) {
+				("play", Some(position)) => Some(parse_number(position)?),
+				(_, Some(id)) => Some(position_of(&lock(state), id)?),
+				(_, None) => None,
+			};
+			ack(PlayerCommand::Play { position }.into())
+		}
+		("pause", []) => ack(PlayerCommand::Toggle.into()),
+		("pause", ["1"]) => ack(PlayerCommand::Pause.into()),
+		("pause", ["0"]) => ack(PlayerCommand::Play { position: None }.into()),
+		("stop", []) => ack(PlayerCommand::Stop.into()),
+		("next", []) => ack(PlayerCommand::Next.into()),
+		("previous", []) => ack(PlayerCommand::Previous.into()),
+		(name @ ("seek" | "seekid"), [song, time]) => {
+			let time = parse_time(time)?;
+			// the song must not move between looking it up and seeking in it
+			let mut state = lock(state);
+			let position = match name {
+				"seek" => parse_number(song)?,
+				_ => position_of(&state, song)?,
+			};
+			let play = PlayerCommand::Play {
+				position: Some(position),
+			};
+			let seek = PlayerCommand::Seek {
+				time: Relative::Absolute(time),
+			};
+			Command::from(play).execute(&mut state)?;
+			Command::from(seek).execute(&mut state)?;
+			Ok(String::new())
+		}
+		("seekcur", [time]) => {
+			let time = time.parse::<TimeOffset>().map_err(Ack::arg)?;
+			ack(PlayerCommand::Seek { time }.into())
+		}
+		("setvol", [level]) => {
+			let level = parse_number::<i64>(level)?;
+			let level = (u8::try_from(level).ok())
+				.and_then(Percentage::new)
+				.ok_or_else(|| Ack::arg(format!("Volume out of range: {level}")))?;
+			ack(PlayerCommand::Volume {
+				level: Relative::Absolute(level),
+			}
+			.into())
+		}
+		("volume", [change]) => {
+			let level = format!("{change}%")
+				.parse::<Relative<Percentage>>()
+				.map_err(Ack::arg)?;
+			ack(PlayerCommand::Volume { level }.into())
+		}
+		("random", [enabled]) => ack(PlayerCommand::Shuffle {
+			enabled: parse_bool(enabled)?,
+		}
+		.into()),
+		(name @ ("repeat" | "single"), [enabled]) => {
+			let enabled = parse_bool(enabled)?;
+			let mode = match (name, enabled, status(state)?.repeat) {
+				("repeat", false, _) => RepeatMode::Off,
+				("repeat", true, RepeatMode::One) | ("single", true, _) => RepeatMode::One,
+				("single", false, RepeatMode::Off) => RepeatMode::Off,
+				_ => RepeatMode::All,
+			};
+			ack(PlayerCommand::Repeat { mode }.into())
+		}
+
+		("add", [uri]) => {
+			let (album, track) = parse_uri(uri)?;
+			ack(QueueCommand::Add { album, track }.into())
+		}
+		("delete", [range]) => {
+			let (position, end) = parse_range(range)?;
+			let count = end.map_or(1, |end| end.saturating_sub(position));
+			ack(QueueCommand::Delete { position, count }.into())
+		}
+		("deleteid", [id]) => {
+			let mut state = lock(state);
+			let delete = QueueCommand::Delete {
+				position: position_of(&state, id)?,
+				count: 1,
+			};
+			Command::from(delete).execute(&mut state)?;
+			Ok(String::new())
+		}
+		("move", [from, to]) => ack(QueueCommand::Move {
+			from: parse_number(from)?,
+			to: parse_number(to)?,
+		}
+		.into()),
+		("clear", []) => ack(QueueCommand::Clear.into()),
+		("playlistid", [..]) => {
+			max_args(1)?;
+			let position = (args.first())
+				.map(|id| position_of(&lock(state), id))
+				.transpose()?;
+			let Reply::Songs(songs) = execute(QueueCommand::List.into(), state)? else {
+				unreachable!("the queue is listed as songs");
+			};
+			Ok((songs.iter())
+				.filter(|song| position.is_none_or(|position| song.position == Some(position)))
+				.map(format_song)
+				.collect())
+		}
+		("playlistinfo", [..]) => {
+			max_args(1)?;
+			let range = args.first().map(|arg| parse_range(arg)).transpose()?;
+			let Reply::Songs(songs) = execute(QueueCommand::List.into(), state)? else {
+				unreachable!("the queue is listed as songs");
+			};
+			if let Some((position, None)) = range
+				&& position >= songs.len()
+			{
+				return Err(ExecuteError::NoSuchPosition(position).into());
+			}
+
+			let in_range = |song: &&Song| {
+				let position = song.position.unwrap_or_default();
+				range.is_none_or(|(start, end)| {
+					position >= start && position < end.unwrap_or(start + 1)
+				})
+			};
+			Ok(songs.iter().filter(in_range).map(format_song).collect())
+		}
+
+		("list", [tag, filters @ ..]) => {
+			let tag = Tag::parse(tag)?;
+			// `list album <artist>` is an old form of `list album artist <artist>`
+			let filters = match (tag, filters) {
+				(Tag::Album, [artist]) => vec![(Tag::Artist, *artist)],
+				_ => parse_filters(filters)?,
+			};
+			let values = (search(state, &filters, true)?.iter())
+				.filter_map(|song| tag.value(song))
+				.collect::<BTreeSet<_>>();
+			Ok(list(tag.name(), values.iter()))
+		}
+		(name @ ("find" | "search"), filters) if !filters.is_empty() => {
+			let filters = parse_filters(filters)?;
+			let songs = search(state, &filters, name == "find")?;
+			Ok(songs.iter().map(format_song).collect())
+		}
+
+		(name, _) if COMMANDS.contains(&name) => Err(Ack::arg(format!(
+			"wrong number of arguments for \"{name}\""
+		))),
+		(name, _) => Err(Ack::new(
+			AckCode::Unknown,
+			format!("unknown command \"{name}\""),
+		)),
+	}
+}
+
+fn execute(command: Command, state: &SharedState) -> Result<Reply, Ack> {
+	Ok(command.execute(&mut lock(state))?)
+}
+
+fn lock(state: &SharedState) -> MutexGuard<'_, State> {
+	state.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn status(state: &SharedState) -> Result<PlayerStatus, Ack> {
+	match execute(PlayerCommand::Status.into(), state)? {
+		Reply::Status(status) => Ok(status),
+		_ => unreachable!("the status command replies with the status"),
+	}
+}
+
+/// The queue position of the entry whose id is given in `arg`
+fn position_of(state: &State, arg: &str) -> Result<usize, Ack> {
+	let id = parse_number(arg)?;
+	(state.player.position_of(id))
+		.ok_or_else(|| Ack::new(AckCode::NoExist, format!("No such song: {id}")))
+}
+
+fn search(state: &SharedState, filters: &[(Tag, &str)], exact: bool) -> Result<Vec<Song>, Ack> {
+	let (mut any, mut artist, mut album, mut title, mut year) = (None, None, None, None, None);
+	for &(tag, value) in filters {
+		let text = Some(value.to_string());
+		match tag {
+			Tag::Any => any = text,
+			Tag::Artist => artist = text,
+			Tag::Album => album = text,
+			Tag::Title => title = text,
+			Tag::Date => year = Some(parse_number(value)?),
+		}
+	}
+	let search = AlbumCommand::Search {
+		any,
+		artist,
+		album,
+		title,
+		year,
+		exact,
+	};
+
+	match execute(search.into(), state)? {
+		Reply::Songs(songs) => Ok(songs),
+		_ => unreachable!("a search replies with songs"),
+	}
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Tag {
+	Any,
+	Artist,
+	Album,
+	Title,
+	Date,
+}
+
+impl Tag {
+	fn parse(name: &str) -> Result<Self, Ack> {
+		match name.to_lowercase().as_str() {
+			"any" => Ok(Self::Any),
+			"artist" | "albumartist" => Ok(Self::Artist),
+			"album" => Ok(Self::Album),
+			"title" => Ok(Self::Title),
+			"date" => Ok(Self::Date),
+			_ => Err(Ack::arg(format!("Unknown tag type: {name}"))),
+		}
+	}
+
+	fn name(self) -> &'static str {
+		match self {
+			Self::Any => "Any",
+			Self::Artist => "Artist",
+			Self::Album => "Album",
+			Self::Title => "Title",
+			Self::Date => "Date",
+		}
+	}
+
+	fn value(self, song: &Song) -> Option<String> {
+		match self {
+			Self::Any => None,
+			Self::Artist => Some(song.artist.clone()),
+			Self::Album => Some(song.album_title.clone()),
+			Self::Title => Some(song.track.title.clone()),
+			Self::Date => song.year.map(|year| year.to_string()),
+		}
+	}
+}
+
+/// Pairs up `<tag> <value>` arguments. Filter expressions like
+/// `(artist == "x")` are not supported
+fn parse_filters<'a>(args: &[&'a str]) -> Result<Vec<(Tag, &'a str)>, Ack> {
+	if !args.len().is_multiple_of(2) {
+		return Err(Ack::arg("filters must be pairs of a tag and a value"));
+	}
+	args.chunks(2)
+		.map(|pair| Ok((Tag::parse(pair[0])?, pair[1])))
+		.collect()
+}
+
+/// Splits a command line into words, which may be quoted with `"` and use
+/// `\` to escape quotes and backslashes
+fn split_args(line: &str) -> Result<Vec<String>, Ack> {
+	let mut args = Vec::new();
+	let mut chars = line.chars().peekable();
+
+	loop {
+		while chars.next_if(|c| c.is_ascii_whitespace()).is_some() {}
+		let Some(first) = chars.next() else {
+			return Ok(args);
+		};
+
+		let mut arg = String::new();
+		if first == '"' {
+			loop {
+				match chars.next() {
+					Some('"') => break,
+					Some('\\') => arg.extend(chars.next()),
+					Some(c) => arg.push(c),
+					None => return Err(Ack::arg("Missing closing '\"'")),
+				}
+			}
+		} else {
+			arg.push(first);
+			while let Some(c) = chars.next_if(|c| !c.is_ascii_whitespace()) {
+				arg.push(c);
+			}
+		}
+		args.push(arg);
+	}
+}
+
+fn parse_number<T: FromStr>(arg: &str) -> Result<T, Ack> {
+	arg.parse()
+		.map_err(|_| Ack::arg(format!("Integer expected: {arg}")))
+}
+
+fn parse_bool(arg: &str) -> Result<bool, Ack> {
+	match arg {
+		"0" => Ok(false),
+		"1" => Ok(true),
+		_ => Err(Ack::arg(format!("Boolean (0/1) expected: {arg}"))),
+	}
+}
+
+fn parse_time(arg: &str) -> Result<Timestamp, Ack> {
+	arg.parse()
+		.map_err(|_| Ack::arg(format!("Time expected: {arg}")))
+}
+
+/// Parses `START:END` or a single position, where the end is exclusive and
+/// may be left out to mean the end of the queue
+fn parse_range(arg: &str) -> Result<(usize, Option<usize>), Ack> {
+	match arg.split_once(':') {
+		Some((start, "")) => Ok((parse_number(start)?, Some(usize::MAX))),
+		Some((start, end)) => Ok((parse_number(start)?, Some(parse_number(end)?))),
+		None => Ok((parse_number(arg)?, None)),
+	}
+}
+
+/// Songs are addressed as `album/track`, and a bare `album` adds all of its
+/// tracks
+fn parse_uri(uri: &str) -> Result<(usize, Option<u32>), Ack> {
+	let uri = uri.trim_end_matches('/');
+	let invalid = || Ack::new(AckCode::NoExist, format!("No such song: {uri}"));
+	match uri.split_once('/') {
+		Some((album, track)) => Ok((
+			album.parse().map_err(|_| invalid())?,
+			Some(track.parse().map_err(|_| invalid())?),
+		)),
+		None => Ok((uri.parse().map_err(|_| invalid())?, None)),
+	}
+}
+
+fn list(key: &str, values: impl Iterator<Item = impl fmt::Display>) -> String {
+	values.map(|value| format!("{key}: {value}\n")).collect()
+}
+
+fn format_song(song: &Song) -> String {
+	let duration = song.track.duration.as_secs_f64();
+	let mut fields = format!("file: {}/{}\n", song.album, song.track.number);
+	fields += &format!("Artist: {}\nAlbumArtist: {}\n", song.artist, song.artist);
+	fields += &format!("Title: {}\nAlbum: {}\n", song.track.title, song.album_title);
+	fields += &format!("Track: {}\n", song.track.number);
+	if let Some(year) = song.year {
+		fields += &format!("Date: {year}\n");
+	}
+	fields += &format!("Time: {}\nduration: {duration:.3}\n", duration.round());
+	if let (Some(position), Some(id)) = (song.position, song.id) {
+		fields += &format!("Pos: {position}\nId: {id}\n");
+	}
+	fields
+}
+
+fn format_status(status: &PlayerStatus) -> String {
+	let state = match status.state {
+		PlaybackState::Playing => "play",
+		PlaybackState::Paused => "pause",
+		PlaybackState::Stopped => "stop",
+	};
+	let (repeat, single) = match status.repeat {
+		RepeatMode::Off => (0, 0),
+		RepeatMode::One => (1, 1),
+		RepeatMode::All => (1, 0),
+	};
+
+	let mut fields = format!("volume: {}\n", status.volume);
+	fields += &format!("repeat: {repeat}\nrandom: {}\n", u8::from(status.shuffle));
+	fields += &format!("single: {single}\nconsume: 0\n");
+	fields += &format!("playlistlength: {}\nstate: {state}\n", status.queue_length);
+	if let Some(song) = &status.current {
+		let (position, id) = (
+			song.position.unwrap_or_default(),
+			song.id.unwrap_or_default(),
+		);
+		let (elapsed, duration) = (status.elapsed.as_secs_f64(), song.track.duration);
+		fields += &format!("song: {position}\nsongid: {id}\n");
+		fields += &format!(
+			"time: {}:{}\nelapsed: {elapsed:.3}\nduration: {:.3}\n",
+			elapsed.round(),
+			duration.as_secs(),
+			duration.as_secs_f64()
+		);
+	}
+	fields
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sonas::library::{Album, Library, Track};
+	use std::{net::Ipv4Addr, time::Duration};
+	use tokio::{
+		io::{AsyncBufReadExt, Lines},
+		net::tcp::OwnedWriteHalf,
+	};
+
+	fn state() -> SharedState {
+		let track = |number, title: &str| Track {
+			number,
+			title: title.to_string(),
+			duration: Duration::from_millis(200_500),
+		};
+		let state = SharedState::default();
+		lock(&state).library = Library {
+			albums: vec![
+				Album {
+					id: 1,
+					title: "First".to_string(),
+					artist: "Someone".to_string(),
+					year: Some(2001),
+					tracks: vec![track(1, "One"), track(2, "Two")],
+				},
+				Album {
+					id: 2,
+					title: "Second".to_string(),
+					artist: "Other \"Quoted\"".to_string(),
+					year: None,
+					tracks: vec![track(1, "Intro")],
+				},
+			],
+		};
+		state
+	}
+
+	#[test]
+	fn splits_quoted_arguments() {
+		assert_eq!(
+			split_args(r#"find artist "Other \"Quoted\"" title  x"#),
+			Ok(vec![
+				"find".to_string(),
+				"artist".to_string(),
+				"Other \"Quoted\"".to_string(),
+				"title".to_string(),
+				"x".to_string(),
+			])
+		);
+		assert!(split_args(r#"find "open"#).is_err());
+	}
+
+	#[test]
+	fn plays_the_queue() {
+		let state = state();
+		assert_eq!(run("add 1", &state), Ok(String::new()));
+		assert_eq!(run("add 2/1", &state), Ok(String::new()));
+		run("play 1", &state).unwrap();
+		run("seekcur 30.5", &state).unwrap();
+		run("setvol 40", &state).unwrap();
+		run("repeat 1", &state).unwrap();
+
+		assert_eq!(
+			run("status", &state),
+			Ok("volume: 40\nrepeat: 1\nrandom: 0\nsingle: 0\nconsume: 0\n\
+				playlistlength: 3\nstate: play\nsong: 1\nsongid: 1\n\
+				time: 31:200\nelapsed: 30.500\nduration: 200.500\n"
+				.to_string())
+		);
+		assert_eq!(
+			run("currentsong", &state),
+			Ok(
+				"file: 1/2\nArtist: Someone\nAlbumArtist: Someone\nTitle: Two\nAlbum: First\n\
+				Track: 2\nDate: 2001\nTime: 201\nduration: 200.500\nPos: 1\nId: 1\n"
+					.to_string()
+			)
+		);
+
+		run("delete 0:2", &state).unwrap();
+		let queue = run("playlistinfo", &state).unwrap();
+		assert!(queue.starts_with("file: 2/1\n") && queue.ends_with("Pos: 0\nId: 2\n"));
+		assert_eq!(
+			run("playlistinfo 3", &state).map_err(|ack| ack.code),
+			Err(AckCode::Arg)
+		);
+	}
+
+	#[test]
+	fn ids_survive_queue_changes() {
+		let state = state();
+		run("add 1", &state).unwrap();
+		run("add 2", &state).unwrap();
+		run("move 2 0", &state).unwrap();
+		run("delete 1", &state).unwrap();
+
+		run("playid 1", &state).unwrap();
+		assert!(
+			run("currentsong", &state)
+				.unwrap()
+				.starts_with("file: 1/2\n")
+		);
+		assert!(
+			run("status", &state)
+				.unwrap()
+				.contains("song: 1\nsongid: 1\n")
+		);
+		assert!(
+			run("playlistid 2", &state)
+				.unwrap()
+				.ends_with("Pos: 0\nId: 2\n")
+		);
+
+		run("deleteid 2", &state).unwrap();
+		assert!(
+			run("playlistinfo", &state)
+				.unwrap()
+				.ends_with("Pos: 0\nId: 1\n")
+		);
+		for command in ["playid 0", "deleteid 2", "seekid 2 10", "playlistid 0"] {
+			assert_eq!(
+				run(command, &state).map_err(|ack| ack.code),
+				Err(AckCode::NoExist),
+				"{command}"
+			);
+		}
+	}
+
+	#[test]
+	fn searches_the_library() {
+		let state = state();
+		assert_eq!(
+			run("list artist", &state),
+			Ok("Artist: Other \"Quoted\"\nArtist: Someone\n".to_string())
+		);
+		assert_eq!(
+			run("list album Someone", &state),
+			Ok("Album: First\n".to_string())
+		);
+		assert_eq!(run("find title one", &state), Ok(String::new()));
+		assert!(
+			run("search title one", &state)
+				.unwrap()
+				.contains("Title: One\n")
+		);
+		assert_eq!(
+			run("search any i date 2001", &state).map(|songs| songs.matches("file:").count()),
+			Ok(2)
+		);
+		assert!(run("find date soon", &state).is_err());
+		assert_eq!(
+			run("find", &state).map_err(|ack| ack.code),
+			Err(AckCode::Arg)
+		);
+	}
+
+	#[test]
+	fn reports_errors_in_command_lists() {
+		let state = state();
+		let commands = ["ping", "add 9", "ping"].map(str::to_string);
+		assert_eq!(
+			run_list(&commands, true, &state),
+			"list_OK\nACK [50@1] {add} no album with id 9\n"
+		);
+		assert_eq!(
+			run("frobnicate", &state).map(|_| ()),
+			Err(Ack::new(AckCode::Unknown, "unknown command \"frobnicate\""))
+		);
+		assert_eq!(
+			run("setvol 150", &state),
+			Err(Ack::arg("Volume out of range: 150"))
+		);
+		assert_eq!(
+			run("setvol -1", &state),
+			Err(Ack::arg("Volume out of range: -1"))
+		);
+
+		let mut list = CommandList::default();
+		let line = "x".repeat(MAX_LINE_LEN);
+		for _ in 0..=MAX_LIST_SIZE / MAX_LINE_LEN {
+			list.push(&line);
+		}
+		assert!(list.commands.is_empty());
+		assert_eq!(
+			list.run(&state),
+			format!("ACK [2@0] {{}} command list is larger than {MAX_LIST_SIZE} bytes\n")
+		);
+		assert_eq!(
+			parse_subsystems("idle player mixer"),
+			Ok(vec![Subsystem::Player, Subsystem::Mixer])
+		);
+	}
+
+	struct Client {
+		sender: OwnedWriteHalf,
+		lines: Lines<BufReader<OwnedReadHalf>>,
+	}
+
+	impl Client {
+		async fn write(&mut self, request: &str) {
+			let request = format!("{request}\n");
+			self.sender.write_all(request.as_bytes()).await.unwrap();
+		}
+
+		/// Reads up to and including the line that ends a response
+		async fn read(&mut self) -> Vec<String> {
+			let mut response = Vec::new();
+			loop {
+				let line = self.lines.next_line().await.unwrap().unwrap();
+				let done = line == "OK" || line.starts_with("ACK ");
+				response.push(line);
+				if done {
+					return response;
+				}
+			}
+		}
+
+		async fn send(&mut self, request: &str) -> Vec<String> {
+			self.write(request).await;
+			self.read().await
+		}
+	}
+
+	#[tokio::test]
+	async fn serves_clients_over_tcp() {
+		let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+		let address = listener.local_addr().unwrap();
+		let state = state();
+		tokio::spawn(serve(listener, state.clone()));
+
+		let (recver, sender) = TcpStream::connect(address).await.unwrap().into_split();
+		let mut client = Client {
+			sender,
+			lines: BufReader::new(recver).lines(),
+		};
+		let greeting = client.lines.next_line().await.unwrap();
+		assert_eq!(greeting.as_deref(), Some("OK MPD 0.23.0"));
+
+		assert_eq!(client.send("ping").await, ["OK"]);
+		assert_eq!(
+			client.send("add 9").await,
+			["ACK [50@0] {add} no album with id 9"]
+		);
+		assert_eq!(
+			client
+				.send("command_list_begin\nadd 1\nadd 2/1\ncommand_list_end")
+				.await,
+			["OK"]
+		);
+		assert_eq!(
+			client
+				.send("command_list_ok_begin\nping\nplay 7\nping\ncommand_list_end")
+				.await,
+			["list_OK", "ACK [2@1] {play} no track at queue position 7"]
+		);
+		let listed = client
+			.send("command_list_ok_begin\nplaylistid 2\nping\ncommand_list_end")
+			.await;
+		assert_eq!(listed.first().map(String::as_str), Some("file: 2/1"));
+		assert_eq!(
+			listed[listed.len() - 4..],
+			["Id: 2", "list_OK", "list_OK", "OK"]
+		);
+
+		// changes made by anyone else wake up an idle client
+		client.write("idle player mixer").await;
+		execute(PlayerCommand::Play { position: None }.into(), &state).unwrap();
+		assert_eq!(client.read().await, ["changed: player", "OK"]);
+
+		client.write("idle mixer").await;
+		assert_eq!(client.send("noidle").await, ["OK"]);
+		assert_eq!(
+			client.send("idle playlist").await,
+			["changed: playlist", "OK"]
+		);
+		assert_eq!(
+			client.send("command_list_end").await,
+			["ACK [1@0] {command_list_end} not in a command list"]
+		);
+
+		// a line that is too long is skipped and fails the list it is in
+		let too_long = format!("ACK [2@0] {{}} the line is longer than {MAX_LINE_LEN} bytes");
+		let before = client.send("status").await;
+		client.write("command_list_begin\nadd 1").await;
+		client.write(&"x".repeat(MAX_LINE_LEN + 1)).await;
+		assert_eq!(
+			client.send("clear\ncommand_list_end").await,
+			[too_long.as_str()]
+		);
+		assert_eq!(
+			client.send(&"x".repeat(MAX_LINE_LEN + 1)).await,
+			[too_long.as_str()]
+		);
+		assert_eq!(client.send("status").await, before);
+	}
+}
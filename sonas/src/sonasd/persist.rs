@@ -0,0 +1,129 @@
+use {
+	sonas::{event::Event, execute::SharedState, persist},
+	std::{
+		io,
+		path::{Path, PathBuf},
+		sync::{Mutex, PoisonError},
+		time::Duration,
+	},
+	tokio::{
+		sync::broadcast::error::RecvError,
+		task,
+		time::{self, Instant},
+	},
+};
+
+/// How long the player has to stay unchanged before its state is written
+const SAVE_DEBOUNCE: Duration = Duration::from_secs(2);
+/// How long a change waits at most while the player keeps changing
+const SAVE_MAX_DELAY: Duration = Duration::from_secs(10);
+
+pub fn restore(state: &SharedState, path: &Path) {
+	match persist::load(path) {
+		Ok(Some(player)) => {
+			state.lock().unwrap_or_else(PoisonError::into_inner).player = player;
+			eprintln!("Restored state from {}", path.display());
+		}
+		Ok(None) => (),
+		Err(e) => {
+			eprintln!("Failed to restore state from {}: {e}", path.display());
+			match persist::set_aside(path) {
+				Ok(backup) => eprintln!("Moved the old state to {}", backup.display()),
+				Err(e) => eprintln!("Failed to move the old state aside: {e}"),
+			}
+		}
+	}
+}
+
+/// Held while the state is written, as saves share the temporary file
+static SAVING: Mutex<()> = Mutex::new(());
+
+/// Waits for a save that is still running, so the state written last is
+/// also the newest
+pub fn save(state: &SharedState, path: &Path) {
+	let _saving = SAVING.lock().unwrap_or_else(PoisonError::into_inner);
+	let player = state
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.player
+		.clone();
+	if let Err(e) = persist::save(path, &player) {
+		eprintln!("Failed to save state to {}: {e}", path.display());
+	}
+}
+
+/// Saves the state once no player event has been emitted for a while, or
+/// once the first unsaved change is old enough
+pub async fn save_on_change(state: SharedState, path: PathBuf) {
+	let mut events = state
+		.lock()
+		.unwrap_or_else(PoisonError::into_inner)
+		.events
+		.subscribe();
+
+	loop {
+		match events.recv().await {
+			Ok(Event::Library { .. }) => continue,
+			Err(RecvError::Closed) => return,
+			Ok(_) | Err(RecvError::Lagged(_)) => (),
+		}
+
+		// wait for the changes to settle
+		let first_change = Instant::now();
+		let mut last_change = first_change;
+		loop {
+			let deadline = save_deadline(first_change, last_change);
+			match time::timeout_at(deadline, events.recv()).await {
+				Err(_) => break,
+				Ok(Err(RecvError::Closed)) => return,
+				Ok(_) => last_change = Instant::now(),
+			}
+		}
+
+		// writing and syncing the file blocks
+		let (state, path) = (state.clone(), path.clone());
+		if let Err(e) = task::spawn_blocking(move || save(&state, &path)).await {
+			eprintln!("Failed to save state: {e}");
+		}
+	}
+}
+
+fn save_deadline(first_change: Instant, last_change: Instant) -> Instant {
+	(last_change + SAVE_DEBOUNCE).min(first_change + SAVE_MAX_DELAY)
+}
+
+/// Resolves on SIGINT, or SIGTERM on unix
+pub async fn terminated() -> io::Result<()> {
+	#[cfg(unix)]
+	{
+		use tokio::signal::unix::{SignalKind, signal};
+
+		let mut terminate = signal(SignalKind::terminate())?;
+		tokio::select! {
+			result = tokio::signal::ctrl_c() => result,
+			_ = terminate.recv() => Ok(()),
+		}
+	}
+	#[cfg(not(unix))]
+	tokio::signal::ctrl_c().await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn saves_are_not_put_off_forever() {
+		let first_change = Instant::now();
+		assert_eq!(
+			save_deadline(first_change, first_change),
+			first_change + SAVE_DEBOUNCE
+		);
+
+		let busy = first_change + SAVE_MAX_DELAY - Duration::from_secs(1);
+		assert_eq!(
+			save_deadline(first_change, busy),
+			first_change + SAVE_MAX_DELAY
+		);
+	}
+}